@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use crate::filter::{get_filter_policy, FilterPolicy};
+use crate::graph::DependencyGraph;
+use crate::history::{get_requested_packages, RequestedSpec};
+use crate::marks::{get_marks, Mark, Marks};
+use crate::metadata::Metadata;
+use crate::package::Installer;
+use crate::utils::{find_metadata, CONDA_METADATA};
+
+/// Returns installed conda packages that are not required, directly or transitively, by any package explicitly
+/// requested by the user (read from `conda-meta/history`), nor by any of given packages to keep.
+///
+/// Packages installed by pip are not recorded in the history, but they were installed explicitly,
+/// so they are kept together with their dependencies (unless `no_pip` is set, then they are not taken into account).
+/// Packages filtered out by the policy (see `FilterPolicy`) are kept together with their dependencies as well.
+/// Packages marked as manually installed (see `Marks`) are kept, packages marked as automatically installed
+/// are kept only if other kept packages depend on them.
+///
+/// If history of the environment cannot be read or any of the packages to keep is not installed,
+/// then an error is returned.
+pub fn get_autoremovable_packages(keep: &[String], no_pip: bool) -> Result<Vec<String>, io::Error> {
+    if let Some(name) = keep
+        .iter()
+        .find(|name| find_metadata(&CONDA_METADATA, name).is_none())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Package '{}' not found", name),
+        ));
+    }
+    let requested = get_requested_packages()?;
+    Ok(autoremovable_packages(
+        &CONDA_METADATA,
+        &requested,
+        keep,
+        no_pip,
+        get_filter_policy(),
+        &get_marks(),
+    ))
+}
+
+#[doc(hidden)]
+fn autoremovable_packages(
+    metadata: &HashMap<String, Metadata>,
+    requested: &[RequestedSpec],
+    keep: &[String],
+    no_pip: bool,
+    policy: &FilterPolicy,
+    marks: &Marks,
+) -> Vec<String> {
+    // roots of the dependency graph walk
+    let mut stack: Vec<&Metadata> = metadata
+        .values()
+        .filter(|m| !no_pip && m.installer() != Installer::Conda)
+        .chain(
+            requested
+                .iter()
+                .filter_map(|r| find_metadata(metadata, &r.name)),
+        )
+        .filter(|m| marks.get(&m.name) != Some(Mark::Auto))
+        .collect();
+    stack.extend(metadata.values().filter(|m| policy.is_excluded(&m.name)));
+    stack.extend(
+        keep.iter()
+            .chain(marks.marked(Mark::Manual).iter())
+            .filter_map(|name| find_metadata(metadata, name)),
+    );
+
+    let graph = DependencyGraph::new(metadata.values());
+    let mut reachable: HashSet<&str> = HashSet::new();
+    while let Some(package) = stack.pop() {
+        if !reachable.insert(&package.name) {
+            continue;
+        }
+        stack.extend(
+            graph
+                .dependencies(&package.name)
+                .iter()
+                .filter(|m| !reachable.contains(m.name.as_str())),
+        );
+    }
+
+    let mut autoremovable: Vec<String> = metadata
+        .values()
+        .filter(|m| m.installer() == Installer::Conda && !reachable.contains(m.name.as_str()))
+        .map(|m| m.name.clone())
+        .collect();
+    autoremovable.sort();
+    autoremovable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{get_conda_meta_metadata, get_prefix_metadata};
+
+    #[test]
+    fn test_autoremovable_packages() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_conda_meta_metadata("./tests/data");
+        let requested = vec![RequestedSpec {
+            name: String::from("pkg2c"),
+            spec: String::from("pkg2c"),
+        }];
+        let policy = FilterPolicy::default();
+        let marks = Marks::default();
+        // when:
+        let autoremovable =
+            autoremovable_packages(&metadata, &requested, &[], false, &policy, &marks);
+        let kept = autoremovable_packages(
+            &metadata,
+            &requested,
+            &[String::from("pkg3"), String::from("pkg3b")],
+            false,
+            &policy,
+            &marks,
+        );
+        // then:
+        // `pkg3` and `pkg3b` are leaves, but nobody asked for them
+        assert_eq!(autoremovable, vec!["pkg2b", "pkg3", "pkg3b"]);
+        assert!(kept.is_empty())
+    }
+
+    #[test]
+    fn test_autoremovable_packages_mixed_environment() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let policy = FilterPolicy::default();
+        let marks = Marks::default();
+        // when:
+        let autoremovable = autoremovable_packages(&metadata, &[], &[], false, &policy, &marks);
+        let conda_autoremovable =
+            autoremovable_packages(&metadata, &[], &[], true, &policy, &marks);
+        // then:
+        // `six` is required by `patsy` installed by pip, `python` is filtered out
+        assert!(autoremovable.is_empty());
+        assert_eq!(conda_autoremovable, vec!["six"])
+    }
+
+    #[test]
+    fn test_autoremovable_packages_marks() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_conda_meta_metadata("./tests/data");
+        let requested = vec![RequestedSpec {
+            name: String::from("pkg2c"),
+            spec: String::from("pkg2c"),
+        }];
+        let mut marks = Marks::default();
+        marks.mark("pkg2b", Mark::Manual);
+        marks.mark("pkg2c", Mark::Auto);
+        // when:
+        let autoremovable = autoremovable_packages(
+            &metadata,
+            &requested,
+            &[],
+            false,
+            &FilterPolicy::default(),
+            &marks,
+        );
+        // then:
+        assert_eq!(
+            autoremovable,
+            vec!["pkg1", "pkg2a", "pkg2c", "pkg3", "pkg3b"]
+        )
+    }
+}
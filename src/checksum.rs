@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+/// SHA-256 checksum recorded during installation, in the encoding used by the installer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Checksum {
+    /// Hexadecimal SHA-256 digest, the way conda records it in `paths_data`.
+    Hex(String),
+    /// URL-safe base64 SHA-256 digest (without padding), the way pip records it in `RECORD`.
+    UrlsafeBase64(String),
+}
+
+impl Checksum {
+    /// Returns true if given SHA-256 digest matches the checksum.
+    pub fn matches(&self, digest: &[u8]) -> bool {
+        match self {
+            Self::Hex(expected) => expected.eq_ignore_ascii_case(&to_hex(digest)),
+            Self::UrlsafeBase64(expected) => *expected == to_urlsafe_base64(digest),
+        }
+    }
+}
+
+/// Returns SHA-256 digest of the file with given path.
+///
+/// If the file cannot be read, then an error is returned.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<[u8; 32], io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Returns lowercase hexadecimal representation of given bytes (the way conda stores checksums).
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns URL-safe base64 representation of given bytes without padding
+/// (the way `RECORD` files of dist-info store checksums).
+pub fn to_urlsafe_base64(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_file() {
+        let digest = sha256_file("./tests/data/mixed-env/lib/python3.8/site-packages/six.py");
+        assert_eq!(
+            to_hex(&digest.unwrap()),
+            "834f6c2e5b4c1308a3667ca1147181ab8b085da2e49aabddca3886a605d2dbe2"
+        );
+        assert!(sha256_file("./tests/data/mixed-env/missing.py").is_err())
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(b""), "");
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab, 0xff]), "000fabff");
+    }
+
+    #[test]
+    fn test_to_urlsafe_base64() {
+        assert_eq!(to_urlsafe_base64(b""), "");
+        assert_eq!(to_urlsafe_base64(b"f"), "Zg");
+        assert_eq!(to_urlsafe_base64(b"fo"), "Zm8");
+        assert_eq!(to_urlsafe_base64(b"foo"), "Zm9v");
+        assert_eq!(to_urlsafe_base64(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn test_checksum_matches() {
+        // given:
+        let digest = [0xfb, 0xff];
+        // then:
+        assert!(Checksum::Hex(String::from("FBFF")).matches(&digest));
+        assert!(Checksum::UrlsafeBase64(String::from("-_8")).matches(&digest));
+        assert!(!Checksum::UrlsafeBase64(String::from("fbff")).matches(&digest))
+    }
+}
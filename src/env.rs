@@ -15,6 +15,33 @@ pub struct CondaEnv {
 }
 
 impl CondaEnv {
+    /// Returns environment made of given leaves of a past revision,
+    /// described by metadata of the revision (see `get_revision_metadata`).
+    pub fn from_revision(leaves: Vec<String>, metadata: &HashMap<String, Metadata>) -> Self {
+        // get conda name
+        let name =
+            env::var("CONDA_DEFAULT_ENV").expect("Please make sure you have conda env activated.");
+        // only names and versions are exported, so dependency tree is not built
+        // (it would be resolved against the current environment, not the revision)
+        let installed_packages: Vec<Package> = leaves
+            .iter()
+            .filter_map(|name| metadata.get(name))
+            .map(|m| {
+                Metadata {
+                    depends: vec![],
+                    requires_dist: vec![],
+                    ..m.to_owned()
+                }
+                .into()
+            })
+            .collect();
+
+        Self {
+            name,
+            installed_packages,
+        }
+    }
+
     // TODO add information about version
     pub fn to_yml<'a, P>(&self, filename: &'a P) -> io::Result<()>
     where
@@ -40,11 +67,11 @@ impl CondaEnv {
             .filter(|&p| p.installer == Installer::Conda)
             .collect();
 
-        // get deps installed by pip
+        // get deps installed by pip (or other Python package installers)
         let pip_deps: Vec<&Package> = self
             .installed_packages
             .iter()
-            .filter(|&p| p.installer == Installer::Pip)
+            .filter(|&p| p.installer != Installer::Conda)
             .collect();
 
         for &d in conda_deps.iter() {
@@ -55,7 +82,7 @@ impl CondaEnv {
         }
         file.write_all(b"\n")?;
 
-        if pip_deps.len() > 0 {
+        if !pip_deps.is_empty() {
             file.write_all(b"  - pip:")?;
             for &d in pip_deps.iter() {
                 let p_string: String = d.to_owned().into();
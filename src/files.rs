@@ -0,0 +1,603 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+
+use crate::metadata::Metadata;
+use crate::package::Installer;
+use crate::utils::{
+    get_conda_meta_metadata, get_conda_prefix, get_pip_metadata, relative_path, CONDA_METADATA,
+};
+
+lazy_static! {
+    #[doc(hidden)]
+    /// File-ownership index of the environment, see `get_file_owners`.
+    pub(crate) static ref FILE_OWNERS: HashMap<String, Vec<String>> =
+        get_file_owners(get_conda_prefix(), &CONDA_METADATA);
+}
+
+/// Returns file-ownership index of environment with given prefix, where:
+/// - `key` - is the path of the file, relative to the prefix (e.g. `bin/python3.8`),
+/// - `value` - is the list of packages that installed the file (more than one if packages clobber each other).
+///
+/// Files of conda packages are taken from their `paths_data` (or `files`) record,
+/// files of Python packages installed by pip from `RECORD` file of their dist-info.
+pub(crate) fn get_file_owners<P: AsRef<Path>>(
+    prefix: P,
+    metadata: &HashMap<String, Metadata>,
+) -> HashMap<String, Vec<String>> {
+    let mut file_owners: HashMap<String, Vec<String>> = HashMap::new();
+    for m in metadata.values() {
+        for file in get_installed_files(&prefix, m) {
+            file_owners.entry(file).or_default().push(m.name.clone())
+        }
+    }
+    for owners in file_owners.values_mut() {
+        owners.sort();
+        owners.dedup();
+    }
+    file_owners
+}
+
+/// Returns paths of files installed by given package, relative to the prefix.
+pub(crate) fn get_installed_files<P: AsRef<Path>>(prefix: P, metadata: &Metadata) -> Vec<String> {
+    match (&metadata.record, &metadata.location) {
+        (Some(record), _) => record
+            .installed_paths()
+            .into_iter()
+            .map(|p| p.to_string())
+            .collect(),
+        (None, Some(location)) => metadata
+            .record_files
+            .iter()
+            .map(|f| relative_path(prefix.as_ref(), &location.join(&f.path)))
+            .collect(),
+        (None, None) => vec![],
+    }
+}
+
+#[doc(hidden)]
+/// Top-level entries of the prefix that are managed by conda rather than installed by packages:
+/// records of the environment and, in the base environment, package cache, other environments and configuration.
+const CONDA_STATE_ENTRIES: [&str; 7] = [
+    "conda-meta",
+    "pkgs",
+    "envs",
+    "conda-bld",
+    ".condarc",
+    "condarc",
+    ".nonadmin",
+];
+
+/// File or directory of the environment that is not owned by any package.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct OrphanFile {
+    /// Path relative to the prefix, directories end with `/`.
+    pub path: String,
+    /// Size in bytes (total size of the files inside for directories).
+    pub size: u64,
+}
+
+/// Returns files and directories of environment with given prefix that are not owned by any package,
+/// together with their sizes (in bytes). Directories are reported as a whole (with `/` at the end),
+/// if none of the files inside is owned by a package.
+///
+/// Entries managed by conda itself (see `CONDA_STATE_ENTRIES`) are skipped, as well as compiled Python files (`.pyc`)
+/// of owned sources.
+pub(crate) fn get_orphan_files<P: AsRef<Path>>(
+    prefix: P,
+    file_owners: &HashMap<String, Vec<String>>,
+) -> Vec<OrphanFile> {
+    let mut orphans = vec![];
+    let entries = match prefix.as_ref().read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return orphans,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if CONDA_STATE_ENTRIES.contains(&name.as_str()) {
+            continue;
+        }
+        let (owned, size, mut entry_orphans) = walk_orphans(&entry.path(), &name, file_owners);
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if !owned && is_dir {
+            orphans.push(OrphanFile {
+                path: format!("{}/", name),
+                size,
+            })
+        } else {
+            orphans.append(&mut entry_orphans)
+        }
+    }
+    orphans.sort();
+    orphans
+}
+
+#[doc(hidden)]
+/// Walks given path (without following symlinks) and returns:
+/// whether any file inside is owned, total size and orphans found inside.
+fn walk_orphans(
+    path: &Path,
+    relative: &str,
+    file_owners: &HashMap<String, Vec<String>>,
+) -> (bool, u64, Vec<OrphanFile>) {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return (false, 0, vec![]),
+    };
+    if !metadata.is_dir() {
+        let owned = is_owned_file(relative, file_owners);
+        let orphans = if owned {
+            vec![]
+        } else {
+            vec![OrphanFile {
+                path: relative.to_string(),
+                size: metadata.len(),
+            }]
+        };
+        return (owned, metadata.len(), orphans);
+    }
+
+    let mut children = vec![];
+    if let Ok(entries) = path.read_dir() {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let child_relative = format!("{}/{}", relative, name);
+            let (owned, size, orphans) = walk_orphans(&entry.path(), &child_relative, file_owners);
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            children.push((is_dir, child_relative, owned, size, orphans))
+        }
+    }
+    let owned = children.iter().any(|c| c.2);
+    let size = children.iter().map(|c| c.3).sum();
+    let mut orphans = vec![];
+    for (is_dir, child_relative, child_owned, child_size, mut child_orphans) in children {
+        if is_dir && !child_owned {
+            orphans.push(OrphanFile {
+                path: format!("{}/", child_relative),
+                size: child_size,
+            })
+        } else {
+            orphans.append(&mut child_orphans)
+        }
+    }
+    (owned, size, orphans)
+}
+
+#[doc(hidden)]
+/// Returns `true` if file is owned by any package.
+/// Compiled Python files (`__pycache__/name.<tag>.pyc` or `name.pyc`) are owned, if their source file is.
+fn is_owned_file(relative: &str, file_owners: &HashMap<String, Vec<String>>) -> bool {
+    if file_owners.contains_key(relative) {
+        return true;
+    }
+    let stem = match relative.strip_suffix(".pyc") {
+        Some(stem) => stem,
+        None => return false,
+    };
+    let source = match stem.rsplit_once("/__pycache__/") {
+        Some((dir, file)) => {
+            let module = file.split('.').next().unwrap_or(file);
+            format!("{}/{}.py", dir, module)
+        }
+        None => format!("{}.py", stem),
+    };
+    file_owners.contains_key(&source)
+}
+
+/// Returns lines of orphan files report, where orphans are grouped by top-level directory of the prefix.
+pub(crate) fn orphan_files_to_lines(orphans: &[OrphanFile]) -> Vec<String> {
+    let mut groups: Vec<(String, Vec<&OrphanFile>)> = vec![];
+    for orphan in orphans.iter() {
+        let top_level = match orphan.path.split_once('/') {
+            Some((top_level, _)) => format!("{}/", top_level),
+            None => String::from("./"),
+        };
+        match groups.iter_mut().find(|(t, _)| *t == top_level) {
+            Some((_, group)) => group.push(orphan),
+            None => groups.push((top_level, vec![orphan])),
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut lines = vec![];
+    for (top_level, group) in groups {
+        let size: u64 = group.iter().map(|o| o.size).sum();
+        lines.push(format!("{} ({})", top_level, format_size(size)));
+        for orphan in group {
+            lines.push(format!("  {} ({})", orphan.path, format_size(orphan.size)));
+        }
+    }
+    lines
+}
+
+/// Package claiming a file, together with its installer.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FileClaimant {
+    pub package: String,
+    pub installer: Installer,
+}
+
+/// Files claimed by the same packages.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ClobberedFiles {
+    /// Packages claiming the files, sorted by name.
+    pub claimants: Vec<FileClaimant>,
+    /// Paths of the files relative to the prefix, sorted.
+    pub files: Vec<String>,
+}
+
+/// Returns files of environment with given prefix that are claimed by more than one package
+/// (conda and conda, or conda and pip), grouped by the packages claiming them.
+///
+/// Unlike `FILE_OWNERS`, conda packages shadowed by packages installed by pip with the same name
+/// are taken into account, so files of conda package overwritten by `pip install --upgrade` are reported as well.
+pub(crate) fn get_clobbered_files<P: AsRef<Path>>(prefix: P) -> Vec<ClobberedFiles> {
+    let prefix = prefix.as_ref();
+    let conda_metadata = get_conda_meta_metadata(prefix);
+    let pip_metadata = get_pip_metadata(prefix, &conda_metadata);
+
+    let mut file_owners: HashMap<String, Vec<FileClaimant>> = HashMap::new();
+    for m in conda_metadata.values().chain(pip_metadata.iter()) {
+        for file in get_installed_files(prefix, m) {
+            file_owners.entry(file).or_default().push(FileClaimant {
+                package: m.name.clone(),
+                installer: m.installer(),
+            })
+        }
+    }
+
+    let mut files: Vec<(String, Vec<FileClaimant>)> = file_owners
+        .into_iter()
+        .map(|(file, mut owners)| {
+            owners.sort_by(|a, b| {
+                (&a.package, a.installer.to_string()).cmp(&(&b.package, b.installer.to_string()))
+            });
+            owners.dedup();
+            (file, owners)
+        })
+        .filter(|(_, owners)| owners.len() > 1)
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut clobbered: Vec<ClobberedFiles> = vec![];
+    for (file, owners) in files {
+        match clobbered.iter_mut().find(|c| c.claimants == owners) {
+            Some(group) => group.files.push(file),
+            None => clobbered.push(ClobberedFiles {
+                claimants: owners,
+                files: vec![file],
+            }),
+        }
+    }
+    clobbered.sort_by(|a, b| a.files[0].cmp(&b.files[0]));
+    clobbered
+}
+
+/// Returns lines of clobbered files report, where files are grouped by the packages claiming them.
+pub(crate) fn clobbered_files_to_lines(clobbered: &[ClobberedFiles]) -> Vec<String> {
+    let mut lines = vec![];
+    for ClobberedFiles { claimants, files } in clobbered.iter() {
+        let owners: Vec<String> = claimants
+            .iter()
+            .map(|c| format!("{} ({})", c.package, c.installer))
+            .collect();
+        let noun = if files.len() == 1 { "file" } else { "files" };
+        lines.push(format!(
+            "{} claim {} {}:",
+            owners.join(", "),
+            files.len(),
+            noun
+        ));
+        for file in files.iter() {
+            lines.push(format!("  {}", file));
+        }
+    }
+    lines
+}
+
+/// Returns human readable representation of given number of bytes, e.g. `1.5 KiB`.
+pub(crate) fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, units[unit])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+/// Packages that installed a file of the environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathOwners {
+    /// Path of the file relative to the environment prefix (the key of `FILE_OWNERS` index).
+    pub path: String,
+    /// Names of the packages that installed the file, empty if no package owns it.
+    pub owners: Vec<String>,
+}
+
+/// Returns packages that installed the file with given path.
+/// If no package owns the file, given path is returned as it is, without owners.
+///
+/// Path can be given as absolute, relative to the current directory or relative to the prefix.
+pub fn get_path_owners<P: AsRef<Path>>(path: P) -> PathOwners {
+    let prefix = PathBuf::from(get_conda_prefix());
+    let path = path.as_ref();
+    let mut candidates = vec![];
+    if path.is_relative() {
+        candidates.push(relative_path(Path::new(""), path));
+    }
+    if let Ok(absolute) = std::env::current_dir().map(|cwd| cwd.join(path)) {
+        if let Ok(absolute_prefix) = std::env::current_dir().map(|cwd| cwd.join(&prefix)) {
+            candidates.push(relative_path(&absolute_prefix, &absolute));
+        }
+        // prefix or path may contain symlinks
+        if let (Ok(canonical_prefix), Ok(canonical)) =
+            (prefix.canonicalize(), absolute.canonicalize())
+        {
+            candidates.push(relative_path(&canonical_prefix, &canonical));
+        }
+    }
+    for candidate in candidates.iter() {
+        if let Some(owners) = FILE_OWNERS.get(candidate) {
+            return PathOwners {
+                path: candidate.clone(),
+                owners: owners.clone(),
+            };
+        }
+    }
+    let key = candidates
+        .pop()
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+    PathOwners {
+        path: key,
+        owners: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_prefix_metadata;
+
+    #[test]
+    fn test_get_file_owners() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/mixed-env";
+        let metadata = get_prefix_metadata(prefix);
+        // when:
+        let file_owners = get_file_owners(prefix, &metadata);
+        // then:
+        let owner = |path: &str| file_owners.get(path).cloned().unwrap_or_default();
+        assert_eq!(owner("bin/python3.8"), vec!["python"]);
+        assert_eq!(owner("lib/python3.8/site-packages/six.py"), vec!["six"]);
+        assert_eq!(owner("bin/patsy-version"), vec!["patsy"]);
+        assert_eq!(
+            owner("lib/python3.8/site-packages/astor-0.8.1.dist-info/RECORD"),
+            vec!["astor"]
+        );
+        assert!(owner("bin/unknown").is_empty())
+    }
+
+    #[test]
+    fn test_get_installed_files_outside_site_packages() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let path = "./tests/data/mixed-env/lib/python3.8/site-packages/patsy-0.5.1.dist-info";
+        // when:
+        let metadata = Metadata::from_dist_info(path).unwrap();
+        // then:
+        assert_eq!(metadata.record_files.len(), 5);
+        assert_eq!(
+            get_installed_files("./tests/data/mixed-env", &metadata)[0],
+            String::from("bin/patsy-version")
+        )
+    }
+
+    #[test]
+    fn test_get_orphan_files() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let orphan = |path: &str, size: u64| OrphanFile {
+            path: String::from(path),
+            size,
+        };
+        let prefix = "./tests/data/mixed-env";
+        let file_owners = get_file_owners(prefix, &get_prefix_metadata(prefix));
+        let expected_orphans = vec![
+            orphan("bin/stray-script", 21),
+            orphan(
+                "lib/python3.8/site-packages/__pycache__/stray.cpython-38.pyc",
+                8,
+            ),
+            orphan("share/", 14),
+        ];
+        // when:
+        let orphans = get_orphan_files(prefix, &file_owners);
+        // then:
+        assert_eq!(orphans, expected_orphans)
+    }
+
+    #[test]
+    fn test_get_orphan_files_base_environment() {
+        // given:
+        let orphan = |path: &str, size: u64| OrphanFile {
+            path: String::from(path),
+            size,
+        };
+        let prefix = std::env::temp_dir().join("conda-leaves-test-orphan-base");
+        let _ = std::fs::remove_dir_all(&prefix);
+        for dir in [
+            "conda-meta",
+            "pkgs/six-1.15.0-py_0",
+            "envs/other/bin",
+            "bin",
+        ]
+        .iter()
+        {
+            std::fs::create_dir_all(prefix.join(dir)).unwrap();
+        }
+        std::fs::write(prefix.join("pkgs/six-1.15.0-py_0/six.py"), "six").unwrap();
+        std::fs::write(prefix.join("envs/other/bin/python"), "python").unwrap();
+        std::fs::write(prefix.join(".condarc"), "channels: []").unwrap();
+        std::fs::write(prefix.join("bin/stray"), "stray").unwrap();
+        // when:
+        let orphans = get_orphan_files(&prefix, &HashMap::new());
+        std::fs::remove_dir_all(&prefix).unwrap();
+        // then:
+        // package cache, other environments and configuration are managed by conda
+        assert_eq!(orphans, vec![orphan("bin/", 5)])
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_get_orphan_files_symlink_to_directory() {
+        // given:
+        let orphan = |path: &str, size: u64| OrphanFile {
+            path: String::from(path),
+            size,
+        };
+        let prefix = std::env::temp_dir().join("conda-leaves-test-orphan-symlink");
+        let _ = std::fs::remove_dir_all(&prefix);
+        std::fs::create_dir_all(prefix.join("share/data")).unwrap();
+        std::fs::write(prefix.join("share/data/file.txt"), "data").unwrap();
+        std::os::unix::fs::symlink("share/data", prefix.join("data-link")).unwrap();
+        let link_size = prefix.join("data-link").symlink_metadata().unwrap().len();
+        // when:
+        let orphans = get_orphan_files(&prefix, &HashMap::new());
+        std::fs::remove_dir_all(&prefix).unwrap();
+        // then:
+        // the link is reported as a file, it isn't followed
+        assert_eq!(
+            orphans,
+            vec![orphan("data-link", link_size), orphan("share/", 4)]
+        )
+    }
+
+    #[test]
+    fn test_orphan_files_to_lines() {
+        // given:
+        let orphan = |path: &str, size: u64| OrphanFile {
+            path: String::from(path),
+            size,
+        };
+        let orphans = vec![
+            orphan("bin/stray-script", 20),
+            orphan("lib/python3.8/site-packages/stray/", 2048),
+            orphan("lib/python3.8/stray.py", 1024),
+            orphan("share/", 14),
+        ];
+        let expected_lines = vec![
+            "bin/ (20 B)",
+            "  bin/stray-script (20 B)",
+            "lib/ (3.0 KiB)",
+            "  lib/python3.8/site-packages/stray/ (2.0 KiB)",
+            "  lib/python3.8/stray.py (1.0 KiB)",
+            "share/ (14 B)",
+            "  share/ (14 B)",
+        ];
+        // when:
+        let lines = orphan_files_to_lines(&orphans);
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_get_clobbered_files() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let claimant = |package: &str, installer: Installer| FileClaimant {
+            package: String::from(package),
+            installer,
+        };
+        let prefix = "./tests/data/clobbered-env";
+        let expected_clobbered = vec![
+            ClobberedFiles {
+                claimants: vec![
+                    claimant("tool-a", Installer::Conda),
+                    claimant("tool-b", Installer::Conda),
+                ],
+                files: vec![String::from("bin/tool")],
+            },
+            ClobberedFiles {
+                claimants: vec![
+                    claimant("opencv-python", Installer::Pip),
+                    claimant("py-opencv", Installer::Conda),
+                ],
+                files: vec![String::from("lib/python3.8/site-packages/cv2/__init__.py")],
+            },
+            ClobberedFiles {
+                claimants: vec![
+                    claimant("requests", Installer::Conda),
+                    claimant("requests", Installer::Pip),
+                ],
+                files: vec![
+                    String::from("lib/python3.8/site-packages/requests/__init__.py"),
+                    String::from("lib/python3.8/site-packages/requests/api.py"),
+                ],
+            },
+        ];
+        // when:
+        let clobbered = get_clobbered_files(prefix);
+        // then:
+        assert_eq!(clobbered, expected_clobbered)
+    }
+
+    #[test]
+    fn test_get_clobbered_files_of_consistent_env() {
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        assert_eq!(get_clobbered_files("./tests/data/mixed-env"), vec![])
+    }
+
+    #[test]
+    fn test_clobbered_files_to_lines() {
+        // given:
+        let claimant = |package: &str, installer: Installer| FileClaimant {
+            package: String::from(package),
+            installer,
+        };
+        let clobbered = vec![
+            ClobberedFiles {
+                claimants: vec![
+                    claimant("tool-a", Installer::Conda),
+                    claimant("tool-b", Installer::Conda),
+                ],
+                files: vec![String::from("bin/tool")],
+            },
+            ClobberedFiles {
+                claimants: vec![
+                    claimant("requests", Installer::Conda),
+                    claimant("requests", Installer::Pip),
+                ],
+                files: vec![
+                    String::from("lib/python3.8/site-packages/requests/__init__.py"),
+                    String::from("lib/python3.8/site-packages/requests/api.py"),
+                ],
+            },
+        ];
+        let expected_lines = vec![
+            "tool-a (conda), tool-b (conda) claim 1 file:",
+            "  bin/tool",
+            "requests (conda), requests (pip) claim 2 files:",
+            "  lib/python3.8/site-packages/requests/__init__.py",
+            "  lib/python3.8/site-packages/requests/api.py",
+        ];
+        // when:
+        let lines = clobbered_files_to_lines(&clobbered);
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB")
+    }
+}
@@ -0,0 +1,132 @@
+use std::io;
+use std::sync::OnceLock;
+
+use glob::Pattern;
+
+#[doc(hidden)]
+/// Packages excluded by default: the interpreter itself and low level libraries,
+/// that would otherwise be a dependency of almost every package in the environment.
+const DEFAULT_EXCLUDE: [&str; 3] = ["python", "lib*", "_*"];
+
+#[doc(hidden)]
+static FILTER_POLICY: OnceLock<FilterPolicy> = OnceLock::new();
+
+/// Policy that decides which packages are filtered out of the dependency graph.
+///
+/// Excluded packages are not treated as dependencies when conda records are parsed,
+/// and they are not reported as dependent packages nor as leaves.
+/// Globs are matched against the whole package name, so excluding `python` keeps `python-dateutil`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterPolicy {
+    /// Packages that are never filtered out, even if they match one of `exclude` globs.
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Default for FilterPolicy {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: DEFAULT_EXCLUDE
+                .iter()
+                .map(|glob| Pattern::new(glob).unwrap())
+                .collect(),
+        }
+    }
+}
+
+impl FilterPolicy {
+    /// Returns policy that doesn't filter out any package.
+    pub fn all() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+
+    /// Returns default policy extended with given include and exclude globs.
+    ///
+    /// If any of the globs is invalid, then an error is returned.
+    pub fn new<T: AsRef<str>>(include: &[T], exclude: &[T]) -> Result<Self, io::Error> {
+        let mut policy = Self {
+            include: parse_globs(include)?,
+            ..Self::default()
+        };
+        policy.exclude.extend(parse_globs(exclude)?);
+        Ok(policy)
+    }
+
+    /// Returns true if the package with given name is filtered out.
+    pub fn is_excluded<T: AsRef<str>>(&self, name: T) -> bool {
+        let name = name.as_ref();
+        self.exclude.iter().any(|p| p.matches(name))
+            && !self.include.iter().any(|p| p.matches(name))
+    }
+}
+
+#[doc(hidden)]
+fn parse_globs<T: AsRef<str>>(globs: &[T]) -> Result<Vec<Pattern>, io::Error> {
+    globs
+        .iter()
+        .map(|glob| {
+            Pattern::new(glob.as_ref()).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid glob '{}': {}", glob.as_ref(), e),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Sets filter policy of the inspected environment. It has to be called before any package is read,
+/// later calls have no effect.
+pub fn set_filter_policy(policy: FilterPolicy) {
+    let _ = FILTER_POLICY.set(policy);
+}
+
+/// Returns filter policy of the inspected environment, the default one if none has been set.
+pub fn get_filter_policy() -> &'static FilterPolicy {
+    FILTER_POLICY.get_or_init(FilterPolicy::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = FilterPolicy::default();
+        assert!(policy.is_excluded("python"));
+        assert!(policy.is_excluded("libgcc-ng"));
+        assert!(policy.is_excluded("_openmp_mutex"));
+        assert!(!policy.is_excluded("python-dateutil"));
+        assert!(!policy.is_excluded("pythran"));
+        assert!(!policy.is_excluded("numpy"))
+    }
+
+    #[test]
+    fn test_policy_include_and_exclude() {
+        // given:
+        let policy = FilterPolicy::new(&["libarchive", "python"], &["jupyter*"]).unwrap();
+        // then:
+        assert!(!policy.is_excluded("libarchive"));
+        assert!(!policy.is_excluded("python"));
+        assert!(policy.is_excluded("libgcc-ng"));
+        assert!(policy.is_excluded("jupyter-core"));
+        assert!(!policy.is_excluded("ipykernel"))
+    }
+
+    #[test]
+    fn test_policy_invalid_glob() {
+        let policy = FilterPolicy::new(&["[lib"], &[]);
+        assert_eq!(policy.unwrap_err().kind(), io::ErrorKind::InvalidInput)
+    }
+
+    #[test]
+    fn test_policy_all() {
+        let policy = FilterPolicy::all();
+        assert!(!policy.is_excluded("python"));
+        assert!(!policy.is_excluded("_openmp_mutex"))
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use crate::metadata::Metadata;
+use crate::utils::canonicalize_name;
+
+/// Dependency graph of packages in the environment, indexed in both directions by package name.
+///
+/// The graph is built once for the whole environment, so PEP 503 canonical names are computed
+/// once per package and per dependency, instead of once for every pair of packages.
+pub struct DependencyGraph<'a> {
+    dependencies: HashMap<&'a str, Vec<&'a Metadata>>,
+    dependents: HashMap<&'a str, Vec<&'a Metadata>>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    /// Returns dependency graph of given packages.
+    ///
+    /// Conda `depends` are matched by exact name, or by PEP 503 canonical name if the dependency was installed
+    /// by pip on top of conda one (e.g. `PyYAML` over `pyyaml`), Python `Requires-Dist` by PEP 503 canonical name,
+    /// either of the package itself or of Python distributions it provides (e.g. `torch` for `pytorch`).
+    pub fn new<I: IntoIterator<Item = &'a Metadata>>(packages: I) -> Self {
+        let packages: Vec<&Metadata> = packages.into_iter().collect();
+
+        let mut by_name: HashMap<&str, Vec<&Metadata>> = HashMap::new();
+        let mut pip_by_canonical: HashMap<String, Vec<&Metadata>> = HashMap::new();
+        let mut by_canonical: HashMap<String, Vec<&Metadata>> = HashMap::new();
+        for package in packages.iter() {
+            by_name.entry(&package.name).or_default().push(package);
+            let canonical = canonicalize_name(&package.name);
+            if package.record.is_none() {
+                pip_by_canonical
+                    .entry(canonical.clone())
+                    .or_default()
+                    .push(package);
+            }
+            by_canonical.entry(canonical).or_default().push(package);
+            for pypi_name in package.pypi_names.iter() {
+                by_canonical
+                    .entry(pypi_name.clone())
+                    .or_default()
+                    .push(package);
+            }
+        }
+
+        let mut dependencies: HashMap<&str, Vec<&Metadata>> = HashMap::new();
+        let mut dependents: HashMap<&str, Vec<&Metadata>> = HashMap::new();
+        for package in packages.iter() {
+            let mut targets: Vec<&Metadata> = vec![];
+            for d in package.depends.iter() {
+                targets.extend(by_name.get(d.name.as_str()).into_iter().flatten());
+                if !pip_by_canonical.is_empty() {
+                    let canonical = canonicalize_name(&d.name);
+                    targets.extend(pip_by_canonical.get(&canonical).into_iter().flatten());
+                }
+            }
+            for r in package.applicable_requires_dist() {
+                let canonical = canonicalize_name(&r.name);
+                targets.extend(by_canonical.get(&canonical).into_iter().flatten());
+            }
+            targets.sort_by(|a, b| a.name.cmp(&b.name));
+            targets.dedup_by(|a, b| a.name == b.name);
+            for target in targets.iter() {
+                dependents.entry(&target.name).or_default().push(package);
+            }
+            dependencies.insert(&package.name, targets);
+        }
+
+        Self {
+            dependencies,
+            dependents,
+        }
+    }
+
+    /// Returns installed dependencies of the package with given name.
+    pub fn dependencies<T: AsRef<str>>(&self, name: T) -> &[&'a Metadata] {
+        self.dependencies
+            .get(name.as_ref())
+            .map(|d| d.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns installed packages that depend on the package with given name.
+    pub fn dependents<T: AsRef<str>>(&self, name: T) -> &[&'a Metadata] {
+        self.dependents
+            .get(name.as_ref())
+            .map(|d| d.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pep508::Requirement;
+
+    #[test]
+    fn test_dependencies() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let mut metadata = Metadata::from_name("pkg3").unwrap();
+        metadata
+            .requires_dist
+            .push(Requirement::parse("ruamel.yaml>=0.16").unwrap());
+        metadata
+            .requires_dist
+            .push(Requirement::parse("torch==1.7.0").unwrap());
+        let package = |name: &str, pypi_names: Vec<&str>| Metadata {
+            name: String::from(name),
+            pypi_names: pypi_names.into_iter().map(String::from).collect(),
+            ..Metadata::from_name("pkg1").unwrap()
+        };
+        let depends_on = |package: &Metadata| {
+            let graph = DependencyGraph::new(vec![&metadata, package]);
+            let depends_on = graph
+                .dependencies(&metadata.name)
+                .iter()
+                .any(|m| m.name == package.name);
+            let dependent = graph
+                .dependents(&package.name)
+                .iter()
+                .any(|m| m.name == metadata.name);
+            assert_eq!(depends_on, dependent);
+            depends_on
+        };
+        // then:
+        assert!(depends_on(&package("pkg2a", vec![])));
+        assert!(!depends_on(&package("PKG2A", vec![])));
+        assert!(depends_on(&Metadata {
+            record: None,
+            ..package("PKG2A", vec![])
+        }));
+        assert!(depends_on(&package("ruamel_yaml", vec![])));
+        assert!(depends_on(&package("Ruamel-YAML", vec![])));
+        assert!(!depends_on(&package("ruamel", vec![])));
+        assert!(depends_on(&package("pytorch", vec!["torch"])));
+        assert!(!depends_on(&package("pytorch", vec![])))
+    }
+
+    #[test]
+    fn test_dependents_not_installed() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = Metadata::from_name("pkg3").unwrap();
+        // when:
+        let graph = DependencyGraph::new(vec![&metadata]);
+        // then:
+        assert!(graph.dependencies("pkg3").is_empty());
+        assert!(graph.dependents("pkg404").is_empty())
+    }
+}
@@ -0,0 +1,969 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::filter::{get_filter_policy, FilterPolicy};
+use crate::graph::DependencyGraph;
+use crate::marks::{apply_marks, get_marks, Marks};
+use crate::matchspec::MatchSpec;
+use crate::metadata::Metadata;
+use crate::package::Installer;
+use crate::utils::{
+    dependents, find_metadata, get_conda_meta_path, get_conda_prefix, leaves, CONDA_METADATA,
+};
+
+/// Package added or removed by a revision, e.g. `+conda-forge/linux-64::numpy-1.19.1-py38h8854b6b_0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryPackage {
+    /// Channel (and subdir) of the package, missing in histories written by old conda versions.
+    pub channel: Option<String>,
+    pub name: String,
+    pub version: String,
+    pub build: String,
+}
+
+impl HistoryPackage {
+    /// Returns new instance of HistoryPackage from given dist string, i.e. `[channel::]name-version-build`.
+    ///
+    /// If the dist string is invalid, then `None` is returned.
+    pub fn parse<T: AsRef<str>>(dist: T) -> Option<Self> {
+        let dist = dist.as_ref().trim();
+        let (channel, dist) = match dist.rsplit_once("::") {
+            Some((channel, dist)) => (Some(channel.to_string()), dist),
+            None => (None, dist),
+        };
+        let mut parts = dist.rsplitn(3, '-');
+        let build = parts.next()?;
+        let version = parts.next()?;
+        let name = parts.next()?;
+        if name.is_empty() || version.is_empty() || build.is_empty() {
+            return None;
+        }
+        Some(Self {
+            channel,
+            name: name.to_string(),
+            version: version.to_string(),
+            build: build.to_string(),
+        })
+    }
+}
+
+/// Change of a single package made by a revision.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackageChange {
+    Added(HistoryPackage),
+    Removed(HistoryPackage),
+    /// Package replaced by another version or build (old and new one).
+    Changed(HistoryPackage, HistoryPackage),
+}
+
+impl PackageChange {
+    /// Returns name of the changed package.
+    pub fn name(&self) -> &str {
+        match self {
+            PackageChange::Added(p) | PackageChange::Removed(p) | PackageChange::Changed(p, _) => {
+                &p.name
+            }
+        }
+    }
+}
+
+impl fmt::Display for PackageChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            PackageChange::Added(p) => write!(f, "+{} {}", p.name, p.version),
+            PackageChange::Removed(p) => write!(f, "-{} {}", p.name, p.version),
+            // build is shown only if version didn't change, e.g. after a rebuild of the package
+            PackageChange::Changed(old, new) if old.version == new.version => write!(
+                f,
+                "{} {} {} -> {} {}",
+                old.name, old.version, old.build, new.version, new.build
+            ),
+            PackageChange::Changed(old, new) => {
+                write!(f, "{} {} -> {}", old.name, old.version, new.version)
+            }
+        }
+    }
+}
+
+/// Single revision of the environment, i.e. one transaction made by conda.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Revision {
+    /// Number of the revision, the one `conda install --revision` expects (starting from 0).
+    pub number: usize,
+    /// Date and time of the revision, as written by conda, e.g. `2020-08-20 10:15:32`.
+    pub date: String,
+    /// Command that made the revision.
+    pub command: Option<String>,
+    pub conda_version: Option<String>,
+    pub added: Vec<HistoryPackage>,
+    pub removed: Vec<HistoryPackage>,
+    /// Specs requested by `create`, `install` and `update` commands.
+    pub update_specs: Vec<String>,
+    /// Specs requested by `remove` command.
+    pub remove_specs: Vec<String>,
+    /// Specs relaxed by conda when it had to update other packages (`# neutered specs:`).
+    pub neutered_specs: Vec<String>,
+}
+
+impl Revision {
+    /// Returns changes made by the revision, sorted by package name.
+    /// A package both removed and added by the revision is reported as changed.
+    pub fn changes(&self) -> Vec<PackageChange> {
+        let mut changes: Vec<PackageChange> = self
+            .removed
+            .iter()
+            .map(
+                |old| match self.added.iter().find(|new| new.name == old.name) {
+                    Some(new) => PackageChange::Changed(old.clone(), new.clone()),
+                    None => PackageChange::Removed(old.clone()),
+                },
+            )
+            .collect();
+        changes.extend(
+            self.added
+                .iter()
+                .filter(|new| !self.removed.iter().any(|old| old.name == new.name))
+                .map(|new| PackageChange::Added(new.clone())),
+        );
+        changes.sort_by(|a, b| a.name().cmp(b.name()));
+        changes
+    }
+}
+
+/// Spec explicitly requested by the user, e.g. `python=3.8` for `python` package.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RequestedSpec {
+    /// Name of the requested package.
+    pub name: String,
+    /// Original spec, as it was written in the history.
+    pub spec: String,
+}
+
+/// Revisions of the environment, read from `conda-meta/history` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub revisions: Vec<Revision>,
+}
+
+impl History {
+    /// Returns history read from given file.
+    ///
+    /// If the file cannot be read, then an error is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(path.as_ref()).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Cannot read '{}': {}", path.as_ref().display(), e),
+            )
+        })?;
+        Ok(Self::parse(content))
+    }
+
+    /// Returns history parsed from content of `conda-meta/history` file.
+    /// Lines that are not understood (or appear before the first revision) are skipped.
+    pub fn parse<T: AsRef<str>>(content: T) -> Self {
+        let mut revisions: Vec<Revision> = vec![];
+        for line in content.as_ref().lines().map(|l| l.trim()) {
+            if let Some(date) = line.strip_prefix("==>").and_then(|l| l.strip_suffix("<==")) {
+                revisions.push(Revision {
+                    number: revisions.len(),
+                    date: date.trim().to_string(),
+                    ..Revision::default()
+                });
+                continue;
+            }
+            let revision = match revisions.last_mut() {
+                Some(revision) => revision,
+                None => continue,
+            };
+            if let Some(dist) = line.strip_prefix('+') {
+                revision.added.extend(HistoryPackage::parse(dist));
+            } else if let Some(dist) = line.strip_prefix('-') {
+                revision.removed.extend(HistoryPackage::parse(dist));
+            } else if let Some(comment) = line.strip_prefix('#') {
+                parse_comment(revision, comment.trim());
+            }
+        }
+        Self { revisions }
+    }
+
+    /// Returns conda packages installed in the environment right after given revision, sorted by name,
+    /// or `None` if there is no such revision.
+    pub fn packages_at(&self, revision: usize) -> Option<Vec<HistoryPackage>> {
+        if revision >= self.revisions.len() {
+            return None;
+        }
+        let mut packages: HashMap<String, HistoryPackage> = HashMap::new();
+        for r in self.revisions.iter().take(revision + 1) {
+            for package in r.removed.iter() {
+                packages.remove(&package.name);
+            }
+            for package in r.added.iter() {
+                packages.insert(package.name.clone(), package.clone());
+            }
+        }
+        let mut packages: Vec<HistoryPackage> = packages.into_values().collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(packages)
+    }
+
+    /// Returns specs explicitly requested by the user, that are still in effect after the last revision,
+    /// sorted by package name.
+    ///
+    /// Specs are replayed the way conda does it: for each revision removed packages are forgotten first,
+    /// then update specs and finally neutered specs replace the earlier spec for the same package.
+    pub fn requested_specs(&self) -> Vec<RequestedSpec> {
+        let mut requested: HashMap<String, String> = HashMap::new();
+        for revision in self.revisions.iter() {
+            for (name, _) in named_specs(&revision.remove_specs) {
+                requested.remove(&name);
+            }
+            for (name, spec) in named_specs(&revision.update_specs) {
+                requested.insert(name, spec);
+            }
+            for (name, spec) in named_specs(&revision.neutered_specs) {
+                requested.insert(name, spec);
+            }
+        }
+        let mut requested: Vec<RequestedSpec> = requested
+            .into_iter()
+            .map(|(name, spec)| RequestedSpec { name, spec })
+            .collect();
+        requested.sort();
+        requested
+    }
+}
+
+#[doc(hidden)]
+fn parse_comment(revision: &mut Revision, comment: &str) {
+    let (key, value) = match comment.split_once(':') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => return,
+    };
+    match key {
+        "cmd" => revision.command = Some(value.to_string()),
+        "conda version" => revision.conda_version = Some(value.to_string()),
+        // old conda versions wrote `install` and `create` specs
+        "update specs" | "install specs" | "create specs" => {
+            revision.update_specs.extend(parse_specs(value))
+        }
+        "remove specs" => revision.remove_specs.extend(parse_specs(value)),
+        "neutered specs" => revision.neutered_specs.extend(parse_specs(value)),
+        _ => (),
+    }
+}
+
+#[doc(hidden)]
+/// Returns specs from Python list representation, e.g. `['numpy', "pandas>=1.1,<2"]`,
+/// or from comma separated list, written by old conda versions.
+fn parse_specs(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let list = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(list) => list,
+        None => {
+            return value
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        }
+    };
+    let mut specs = vec![];
+    let mut quote: Option<char> = None;
+    let mut spec = String::new();
+    for c in list.chars() {
+        match quote {
+            Some(q) if c == q => {
+                specs.push(spec.trim().to_string());
+                spec.clear();
+                quote = None;
+            }
+            Some(_) => spec.push(c),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None => (),
+        }
+    }
+    specs.retain(|s| !s.is_empty());
+    specs
+}
+
+#[doc(hidden)]
+/// Returns package names of given specs, together with the specs. Invalid specs are skipped.
+fn named_specs(specs: &[String]) -> impl Iterator<Item = (String, String)> + '_ {
+    specs.iter().filter_map(|spec| {
+        MatchSpec::parse(spec)
+            .ok()
+            .map(|matchspec| (matchspec.name, spec.clone()))
+    })
+}
+
+/// Returns history of the environment, read from `conda-meta/history` file.
+///
+/// If the file cannot be read, then an error is returned.
+pub fn get_history() -> Result<History, io::Error> {
+    History::from_file(get_conda_meta_path().join("history"))
+}
+
+/// Returns packages explicitly requested by the user (see `History::requested_specs`),
+/// sorted by package name.
+pub fn get_requested_packages() -> Result<Vec<RequestedSpec>, io::Error> {
+    Ok(get_history()?.requested_specs())
+}
+
+/// Returns directories of conda package cache: the ones from `CONDA_PKGS_DIRS` (comma separated) if it's set,
+/// otherwise `pkgs` directory of the root prefix (for environments in `<root>/envs/<name>`),
+/// of the environment itself (for the base environment) and of the user's home directory.
+fn get_pkgs_dirs<P: AsRef<Path>>(prefix: P) -> Vec<PathBuf> {
+    if let Ok(pkgs_dirs) = std::env::var("CONDA_PKGS_DIRS") {
+        return pkgs_dirs
+            .split(',')
+            .filter(|d| !d.trim().is_empty())
+            .map(|d| PathBuf::from(d.trim()))
+            .collect();
+    }
+    let prefix = prefix.as_ref();
+    let mut pkgs_dirs = vec![prefix.join("../../pkgs"), prefix.join("pkgs")];
+    if let Ok(home) = std::env::var("HOME") {
+        pkgs_dirs.push(Path::new(&home).join(".conda/pkgs"));
+    }
+    pkgs_dirs
+}
+
+/// Metadata of conda packages installed in the environment right after a past revision.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RevisionMetadata {
+    /// Metadata of the packages, indexed by name.
+    pub packages: HashMap<String, Metadata>,
+    /// Names of packages found neither in `conda-meta` nor in package cache, so their dependencies are unknown.
+    pub unresolved: Vec<String>,
+}
+
+#[doc(hidden)]
+/// Returns metadata of given conda packages of a past revision.
+/// Metadata is taken from `conda-meta` if the same build is still installed, from `info/index.json`
+/// of extracted package in package cache otherwise. Packages found in neither of them have no dependencies
+/// and they are reported as unresolved.
+fn revision_metadata(
+    conda_metadata: &HashMap<String, Metadata>,
+    packages: &[HistoryPackage],
+    pkgs_dirs: &[PathBuf],
+) -> RevisionMetadata {
+    let mut revision_metadata = RevisionMetadata::default();
+    for p in packages.iter() {
+        let installed = conda_metadata.get(&p.name).filter(|m| {
+            m.record
+                .as_ref()
+                .is_some_and(|r| r.version == p.version && r.build == p.build)
+        });
+        let cached = || {
+            pkgs_dirs
+                .iter()
+                .map(|d| {
+                    d.join(format!("{}-{}-{}", p.name, p.version, p.build))
+                        .join("info/index.json")
+                })
+                .find_map(|path| Metadata::from_json(&path).ok())
+        };
+        let metadata = match installed {
+            Some(m) => m.clone(),
+            None => cached().unwrap_or_else(|| {
+                revision_metadata.unresolved.push(p.name.clone());
+                Metadata::from_history_package(p)
+            }),
+        };
+        revision_metadata.packages.insert(p.name.clone(), metadata);
+    }
+    revision_metadata.unresolved.sort();
+    revision_metadata
+}
+
+/// Returns metadata of conda packages installed in the environment right after given revision
+/// (see `History::packages_at`), or `None` if there is no such revision.
+/// A warning is printed for every package whose dependencies are unknown (see `RevisionMetadata::unresolved`).
+pub fn get_revision_metadata(history: &History, revision: usize) -> Option<RevisionMetadata> {
+    let packages = history.packages_at(revision)?;
+    let metadata = revision_metadata(
+        &CONDA_METADATA,
+        &packages,
+        &get_pkgs_dirs(get_conda_prefix()),
+    );
+    for name in metadata.unresolved.iter() {
+        warn_unresolved(name);
+    }
+    Some(metadata)
+}
+
+#[doc(hidden)]
+fn warn_unresolved(name: &str) {
+    log::warn!(
+        "Package '{}' is neither installed nor in package cache, its dependencies are unknown",
+        name
+    );
+}
+
+/// Returns leaves of conda packages of a past revision, given their metadata (see `get_revision_metadata`).
+/// Packages filtered out by the policy (see `FilterPolicy`) are not reported.
+/// Marks of the user (see `Marks`) take precedence over the dependency graph.
+pub fn get_revision_leaves(metadata: &HashMap<String, Metadata>) -> Vec<String> {
+    apply_marks(
+        leaves(metadata, true, get_filter_policy()),
+        metadata,
+        true,
+        &get_marks(),
+    )
+}
+
+/// Returns leaves (see `get_leaves`) of the environment right after every revision of given history.
+/// Packages installed by pip are not recorded in the history, so only conda packages are taken into account.
+/// Current marks of the user (see `Marks`) take precedence over the dependency graph of every revision.
+pub fn get_history_leaves(history: &History) -> Vec<Vec<String>> {
+    history_leaves(
+        history,
+        &CONDA_METADATA,
+        &get_pkgs_dirs(get_conda_prefix()),
+        get_filter_policy(),
+        &get_marks(),
+    )
+}
+
+#[doc(hidden)]
+fn history_leaves(
+    history: &History,
+    conda_metadata: &HashMap<String, Metadata>,
+    pkgs_dirs: &[PathBuf],
+    policy: &FilterPolicy,
+    marks: &Marks,
+) -> Vec<Vec<String>> {
+    let mut unresolved: HashSet<String> = HashSet::new();
+    let history_leaves = (0..history.revisions.len())
+        .map(|revision| {
+            let packages = history.packages_at(revision).unwrap_or_default();
+            let metadata = revision_metadata(conda_metadata, &packages, pkgs_dirs);
+            unresolved.extend(metadata.unresolved);
+            let packages = metadata.packages;
+            apply_marks(leaves(&packages, true, policy), &packages, true, marks)
+        })
+        .collect();
+    // the same package is usually part of many revisions, so it's reported once
+    let mut unresolved: Vec<String> = unresolved.into_iter().collect();
+    unresolved.sort();
+    for name in unresolved.iter() {
+        warn_unresolved(name);
+    }
+    history_leaves
+}
+
+/// Returns lines of history report: date, command and package changes of every revision,
+/// followed by changes of leaves, if leaves after every revision are given.
+pub(crate) fn history_to_lines(history: &History, leaves: Option<&[Vec<String>]>) -> Vec<String> {
+    let mut lines = vec![];
+    for revision in history.revisions.iter() {
+        lines.push(format!(
+            "rev {} ({}): {}",
+            revision.number,
+            revision.date,
+            revision.command.as_deref().unwrap_or("unknown command")
+        ));
+        for change in revision.changes() {
+            lines.push(format!("  {}", change));
+        }
+        let leaves = match leaves {
+            Some(leaves) => leaves,
+            None => continue,
+        };
+        let before = match revision.number {
+            0 => &[][..],
+            n => &leaves[n - 1][..],
+        };
+        let after = &leaves[revision.number][..];
+        let mut changes: Vec<String> = after
+            .iter()
+            .filter(|l| !before.contains(l))
+            .map(|l| format!("+{}", l))
+            .collect();
+        changes.extend(
+            before
+                .iter()
+                .filter(|l| !after.contains(l))
+                .map(|l| format!("-{}", l)),
+        );
+        match changes.is_empty() {
+            true => lines.push(String::from("  leaves: unchanged")),
+            false => lines.push(format!("  leaves: {}", changes.join(", "))),
+        }
+    }
+    lines
+}
+
+/// Disagreement between packages requested by the user and leaves of the dependency graph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Disagreement {
+    /// Requested package (with its spec) that is not installed anymore, e.g. uninstalled with `--force`.
+    NotInstalled(String, String),
+    /// Requested package that is not a leaf, because other packages depend on it.
+    RequiredBy(String, Vec<String>),
+    /// Leaf that has never been requested, e.g. installed by pip or left behind by removed package.
+    NotRequested(String, Installer),
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Disagreement::NotInstalled(name, spec) => {
+                write!(
+                    f,
+                    "{} is requested ({}), but it's not installed",
+                    name, spec
+                )
+            }
+            Disagreement::RequiredBy(name, dependents) => write!(
+                f,
+                "{} is requested, but it's required by {}",
+                name,
+                dependents.join(", ")
+            ),
+            Disagreement::NotRequested(name, installer) => write!(
+                f,
+                "{} is a leaf, but it has never been requested (installed by {})",
+                name, installer
+            ),
+        }
+    }
+}
+
+/// Returns a list of disagreements between packages requested by the user (read from `conda-meta/history`)
+/// and leaves (see `get_leaves`). If `no_pip` is set, packages installed by pip are not taken into account.
+/// Packages filtered out by the policy (see `FilterPolicy`) are not reported.
+///
+/// If history of the environment cannot be read, then an error is returned.
+pub fn get_disagreements(no_pip: bool) -> Result<Vec<Disagreement>, io::Error> {
+    let requested = get_requested_packages()?;
+    Ok(disagreements(
+        &CONDA_METADATA,
+        &requested,
+        no_pip,
+        get_filter_policy(),
+    ))
+}
+
+#[doc(hidden)]
+fn disagreements(
+    metadata: &HashMap<String, Metadata>,
+    requested: &[RequestedSpec],
+    no_pip: bool,
+    policy: &FilterPolicy,
+) -> Vec<Disagreement> {
+    let graph = DependencyGraph::new(metadata.values());
+    let mut disagreements = vec![];
+    let mut requested_names = HashSet::new();
+    for r in requested.iter().filter(|r| !policy.is_excluded(&r.name)) {
+        let package = match find_metadata(metadata, &r.name) {
+            Some(package) => package,
+            None => {
+                disagreements.push(Disagreement::NotInstalled(r.name.clone(), r.spec.clone()));
+                continue;
+            }
+        };
+        requested_names.insert(package.name.clone());
+        let mut dependents: Vec<String> = dependents(&graph, &package.name, no_pip).collect();
+        if !dependents.is_empty() {
+            dependents.sort();
+            disagreements.push(Disagreement::RequiredBy(package.name.clone(), dependents));
+        }
+    }
+    for leaf in leaves(metadata, no_pip, policy) {
+        if !requested_names.contains(&leaf) {
+            let installer = metadata[&leaf].installer();
+            disagreements.push(Disagreement::NotRequested(leaf, installer));
+        }
+    }
+    disagreements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_prefix_metadata;
+
+    #[test]
+    fn test_history_package_parse() {
+        assert_eq!(
+            HistoryPackage::parse("conda-forge/linux-64::python-dateutil-2.8.1-py_0"),
+            Some(HistoryPackage {
+                channel: Some(String::from("conda-forge/linux-64")),
+                name: String::from("python-dateutil"),
+                version: String::from("2.8.1"),
+                build: String::from("py_0"),
+            })
+        );
+        assert_eq!(
+            HistoryPackage::parse("six-1.15.0-py_0"),
+            Some(HistoryPackage {
+                channel: None,
+                name: String::from("six"),
+                version: String::from("1.15.0"),
+                build: String::from("py_0"),
+            })
+        );
+        assert_eq!(HistoryPackage::parse("six-1.15.0"), None);
+    }
+
+    #[test]
+    fn test_parse_specs() {
+        assert_eq!(
+            parse_specs(r#"['python=3.8', "pandas>=1.1,<2"]"#),
+            vec!["python=3.8", "pandas>=1.1,<2"]
+        );
+        assert_eq!(parse_specs("python=3.8,six"), vec!["python=3.8", "six"]);
+        assert!(parse_specs("[]").is_empty());
+    }
+
+    #[test]
+    fn test_history_from_file() {
+        // given:
+        let path = "./tests/data/mixed-env/conda-meta/history";
+        // when:
+        let history = History::from_file(path).unwrap();
+        // then:
+        assert_eq!(history.revisions.len(), 4);
+        let revision = &history.revisions[1];
+        assert_eq!(revision.number, 1);
+        assert_eq!(revision.date, "2020-08-21 09:00:00");
+        assert_eq!(
+            revision.command,
+            Some(String::from("/opt/conda/bin/conda install six=1.14 pandas"))
+        );
+        assert_eq!(revision.conda_version, Some(String::from("4.8.4")));
+        let added: Vec<&str> = revision.added.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(added, vec!["pandas", "six"]);
+        assert!(revision.removed.is_empty());
+        assert_eq!(revision.update_specs, vec!["six=1.14", "pandas>=1.1,<2"]);
+        assert_eq!(history.revisions[3].remove_specs, vec!["pandas"]);
+    }
+
+    #[test]
+    fn test_revision_changes() {
+        // given:
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        // when:
+        let changes: Vec<String> = history
+            .revisions
+            .iter()
+            .flat_map(|r| r.changes())
+            .map(|c| c.to_string())
+            .collect();
+        // then:
+        assert_eq!(
+            changes,
+            vec![
+                "+python 3.8.5",
+                "+pandas 1.1.0",
+                "+six 1.14.0",
+                "six 1.14.0 -> 1.15.0",
+                "-pandas 1.1.0"
+            ]
+        )
+    }
+
+    #[test]
+    fn test_package_change_rebuild() {
+        let change = PackageChange::Changed(
+            HistoryPackage::parse("six-1.15.0-py_0").unwrap(),
+            HistoryPackage::parse("six-1.15.0-py_1").unwrap(),
+        );
+        assert_eq!(change.to_string(), "six 1.15.0 py_0 -> 1.15.0 py_1")
+    }
+
+    #[test]
+    fn test_packages_at() {
+        // given:
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        let dists = |revision: usize| -> Vec<String> {
+            history
+                .packages_at(revision)
+                .unwrap()
+                .iter()
+                .map(|p| format!("{}-{}-{}", p.name, p.version, p.build))
+                .collect()
+        };
+        // then:
+        assert_eq!(dists(0), vec!["python-3.8.5-h7579374_1"]);
+        assert_eq!(
+            dists(2),
+            vec![
+                "pandas-1.1.0-py38h950e882_0",
+                "python-3.8.5-h7579374_1",
+                "six-1.15.0-py_0"
+            ]
+        );
+        assert_eq!(dists(3), vec!["python-3.8.5-h7579374_1", "six-1.15.0-py_0"]);
+        assert_eq!(history.packages_at(4), None)
+    }
+
+    #[test]
+    fn test_history_from_missing_file() {
+        let history = History::from_file("./tests/data/clobbered-env/conda-meta/history");
+        assert_eq!(history.unwrap_err().kind(), io::ErrorKind::NotFound)
+    }
+
+    #[test]
+    fn test_requested_specs() {
+        // given:
+        let requested = |name: &str, spec: &str| RequestedSpec {
+            name: String::from(name),
+            spec: String::from(spec),
+        };
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        // when:
+        let requested_specs = history.requested_specs();
+        // then:
+        assert_eq!(
+            requested_specs,
+            vec![requested("python", "python=3.8"), requested("six", "six"),]
+        )
+    }
+
+    #[test]
+    fn test_requested_specs_neutered() {
+        // given:
+        let requested = |name: &str, spec: &str| RequestedSpec {
+            name: String::from(name),
+            spec: String::from(spec),
+        };
+        let history = History::parse(
+            "==> 2021-01-01 00:00:00 <==\n\
+             # cmd: conda create -n test python=3.8 numpy=1.18\n\
+             +defaults/linux-64::python-3.8.5-h7579374_1\n\
+             +defaults/linux-64::numpy-1.18.5-py38_0\n\
+             # update specs: ['python=3.8', 'numpy=1.18']\n\
+             ==> 2021-01-02 00:00:00 <==\n\
+             # cmd: conda install scipy\n\
+             -defaults/linux-64::numpy-1.18.5-py38_0\n\
+             +defaults/linux-64::numpy-1.19.2-py38_0\n\
+             +defaults/linux-64::scipy-1.5.2-py38_0\n\
+             # update specs: ['scipy']\n\
+             # neutered specs: ['numpy', 'pandas']\n",
+        );
+        // when:
+        let requested_specs = history.requested_specs();
+        // then:
+        assert_eq!(
+            requested_specs,
+            vec![
+                requested("numpy", "numpy"),
+                requested("pandas", "pandas"),
+                requested("python", "python=3.8"),
+                requested("scipy", "scipy"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_requested_specs_removed_and_updated() {
+        // given:
+        let requested = |name: &str, spec: &str| RequestedSpec {
+            name: String::from(name),
+            spec: String::from(spec),
+        };
+        let history = History::parse(
+            "==> 2021-01-01 00:00:00 <==\n\
+             # cmd: conda create -n test python=3.8 six\n\
+             +defaults/linux-64::python-3.8.5-h7579374_1\n\
+             +defaults/noarch::six-1.15.0-py_0\n\
+             # update specs: ['python=3.8', 'six']\n\
+             ==> 2021-01-02 00:00:00 <==\n\
+             # cmd: conda update six\n\
+             -defaults/noarch::six-1.15.0-py_0\n\
+             +defaults/noarch::six-1.16.0-py_0\n\
+             # remove specs: ['six']\n\
+             # update specs: ['six>=1.16']\n",
+        );
+        // when:
+        let requested_specs = history.requested_specs();
+        // then:
+        assert_eq!(
+            requested_specs,
+            vec![
+                requested("python", "python=3.8"),
+                requested("six", "six>=1.16"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_disagreements() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let mut requested = History::from_file("./tests/data/mixed-env/conda-meta/history")
+            .unwrap()
+            .requested_specs();
+        requested.push(RequestedSpec {
+            name: String::from("pandas"),
+            spec: String::from("pandas>=1.1"),
+        });
+        requested.sort();
+        // when:
+        let all_disagreements =
+            disagreements(&metadata, &requested, false, &FilterPolicy::default());
+        let conda_disagreements =
+            disagreements(&metadata, &requested, true, &FilterPolicy::default());
+        // then:
+        // `python` is filtered out by default
+        assert_eq!(
+            all_disagreements,
+            vec![
+                Disagreement::NotInstalled(String::from("pandas"), String::from("pandas>=1.1")),
+                Disagreement::RequiredBy(String::from("six"), vec![String::from("patsy")]),
+                Disagreement::NotRequested(String::from("legacy-pkg"), Installer::Pip),
+                Disagreement::NotRequested(String::from("patsy"), Installer::Pip),
+            ]
+        );
+        assert_eq!(
+            conda_disagreements,
+            vec![Disagreement::NotInstalled(
+                String::from("pandas"),
+                String::from("pandas>=1.1")
+            )]
+        );
+        assert_eq!(
+            all_disagreements[1].to_string(),
+            "six is requested, but it's required by patsy"
+        )
+    }
+
+    #[test]
+    fn test_history_leaves() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        let pkgs_dirs = vec![PathBuf::from("./tests/data/pkgs")];
+        // when:
+        let cached_leaves = history_leaves(
+            &history,
+            &metadata,
+            &pkgs_dirs,
+            &FilterPolicy::default(),
+            &Marks::default(),
+        );
+        let uncached_leaves = history_leaves(
+            &history,
+            &metadata,
+            &[],
+            &FilterPolicy::default(),
+            &Marks::default(),
+        );
+        // then:
+        // `pandas` requires `six`, but it's known only from package cache
+        assert_eq!(
+            cached_leaves,
+            vec![vec![], vec!["pandas"], vec!["pandas"], vec!["six"]]
+        );
+        assert_eq!(
+            uncached_leaves,
+            vec![
+                vec![],
+                vec!["pandas", "six"],
+                vec!["pandas", "six"],
+                vec!["six"]
+            ]
+        )
+    }
+
+    #[test]
+    fn test_revision_metadata() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        let packages = history.packages_at(1).unwrap();
+        let pkgs_dirs = vec![PathBuf::from("./tests/data/pkgs")];
+        // when:
+        let revision_metadata = revision_metadata(&metadata, &packages, &pkgs_dirs);
+        // then:
+        assert!(revision_metadata.unresolved.is_empty());
+        let revision_metadata = revision_metadata.packages;
+        let mut names: Vec<&String> = revision_metadata.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["pandas", "python", "six"]);
+        // `six` 1.14.0 is not installed anymore, so it's read from package cache
+        assert_eq!(revision_metadata["six"].version, "1.14.0");
+        assert_eq!(revision_metadata["six"].installer(), Installer::Conda);
+        assert_eq!(revision_metadata["python"], metadata["python"]);
+        let pandas_depends: Vec<&str> = revision_metadata["pandas"]
+            .depends
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(
+            pandas_depends,
+            vec!["numpy", "python-dateutil", "pytz", "six"]
+        )
+    }
+
+    #[test]
+    fn test_revision_metadata_not_cached() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        let packages = history.packages_at(1).unwrap();
+        // when:
+        let revision_metadata = revision_metadata(&HashMap::new(), &packages, &[]);
+        // then:
+        assert_eq!(
+            revision_metadata.unresolved,
+            vec!["pandas", "python", "six"]
+        );
+        let pandas = &revision_metadata.packages["pandas"];
+        assert_eq!(pandas.version, "1.1.0");
+        assert!(pandas.depends.is_empty());
+        assert_eq!(pandas.installer(), Installer::Conda);
+        assert_eq!(pandas.record.as_ref().unwrap().build, "py38h950e882_0")
+    }
+
+    #[test]
+    fn test_history_to_lines() {
+        // given:
+        let history = History::from_file("./tests/data/mixed-env/conda-meta/history").unwrap();
+        let leaves = vec![
+            vec![],
+            vec![String::from("pandas")],
+            vec![String::from("pandas")],
+            vec![String::from("six")],
+        ];
+        let expected_lines = vec![
+            "rev 0 (2020-08-20 10:15:32): /opt/conda/bin/conda create -p /opt/conda/envs/mixed-env python=3.8",
+            "  +python 3.8.5",
+            "  leaves: unchanged",
+            "rev 1 (2020-08-21 09:00:00): /opt/conda/bin/conda install six=1.14 pandas",
+            "  +pandas 1.1.0",
+            "  +six 1.14.0",
+            "  leaves: +pandas",
+            "rev 2 (2020-08-22 12:30:00): /opt/conda/bin/conda update six",
+            "  six 1.14.0 -> 1.15.0",
+            "  leaves: unchanged",
+            "rev 3 (2020-08-23 08:00:00): /opt/conda/bin/conda remove pandas",
+            "  -pandas 1.1.0",
+            "  leaves: +six, -pandas",
+        ];
+        // when:
+        let lines = history_to_lines(&history, Some(&leaves));
+        // then:
+        assert_eq!(lines, expected_lines);
+        assert_eq!(history_to_lines(&history, None).len(), 9)
+    }
+
+    #[test]
+    fn test_get_pkgs_dirs() {
+        let pkgs_dirs = get_pkgs_dirs("/opt/conda/envs/test");
+        assert_eq!(
+            pkgs_dirs[0],
+            PathBuf::from("/opt/conda/envs/test/../../pkgs")
+        );
+        assert_eq!(pkgs_dirs[1], PathBuf::from("/opt/conda/envs/test/pkgs"))
+    }
+}
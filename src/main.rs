@@ -1,28 +1,69 @@
+mod autoremove;
+mod checksum;
 mod env;
+mod files;
+mod filter;
+mod graph;
+mod history;
+mod marks;
+mod matchspec;
 mod metadata;
 mod package;
+mod pep440;
+mod pep508;
 mod utils;
+mod verify;
+mod version;
 
 use std::io;
 use std::path::PathBuf;
 
 use structopt::StructOpt;
 
+use crate::autoremove::get_autoremovable_packages;
 use crate::env::CondaEnv;
+use crate::files::{
+    clobbered_files_to_lines, get_clobbered_files, get_orphan_files, get_path_owners,
+    orphan_files_to_lines, PathOwners, FILE_OWNERS,
+};
+use crate::filter::{set_filter_policy, FilterPolicy};
+use crate::history::{
+    get_disagreements, get_history, get_history_leaves, get_requested_packages,
+    get_revision_leaves, get_revision_metadata, history_to_lines,
+};
+use crate::marks::{mark_packages, Mark};
 use crate::metadata::Metadata;
-use crate::package::{print_package, Package};
-use crate::utils::{get_dependent_packages, get_leaves};
+use crate::package::{print_package, print_package_extras, print_package_info, Package};
+use crate::utils::{
+    get_conda_prefix, get_dependent_packages, get_leaves, get_unsatisfied_dependencies,
+    get_violated_constraints, read_pypi_mapping, set_pypi_mapping,
+};
+use crate::verify::get_verification_problems;
 
 /// Simple CLI tool that allows to pretty print all dependencies within conda environment
 #[derive(Debug, StructOpt)]
 #[structopt(name = "conda-leaves")]
 struct Opts {
-    // TODO it causes CLI much more complex to handle
-    //  I should use it to keep the state of the Opts struct, not to match on it
-    /// Prints packages installed by conda only
+    /// Takes into account packages installed by conda only
     #[structopt(long)]
     no_pip: bool,
 
+    /// Takes into account all packages, including ones filtered out by default (`python`, `lib*`, `_*`)
+    #[structopt(long)]
+    all: bool,
+
+    /// Glob of packages that should never be filtered out (can be used multiple times)
+    #[structopt(long, number_of_values = 1)]
+    include: Vec<String>,
+
+    /// Glob of packages that should be filtered out, on top of the default ones (can be used multiple times)
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Json file with conda -> PyPI name mapping, e.g. `{"py-opencv": "opencv-python"}`
+    #[structopt(long, env = "CONDA_LEAVES_PYPI_MAPPING", parse(from_os_str))]
+    pypi_mapping: Option<PathBuf>,
+
     #[structopt(subcommand)]
     commands: Option<Commands>,
 }
@@ -36,6 +77,59 @@ enum Commands {
         /// Prints libraries that depends on a given package
         #[structopt(short = "d", long)]
         dependent_packages: bool,
+        /// Prints details of the package (build, channel, size, etc.)
+        #[structopt(short = "i", long)]
+        info: bool,
+        /// Prints requirements of every extra declared by the package and whether they are installed
+        #[structopt(short = "e", long)]
+        extras: bool,
+    },
+    /// Checks if installed packages satisfy version and build constraints of their dependencies and constrains
+    Check,
+    /// Prints the package that installed given file of the environment
+    Owner {
+        /// Path of the file, absolute or relative to the environment prefix
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Prints files and directories of the environment that are not owned by any package
+    OrphansFiles,
+    /// Prints files of the environment claimed by more than one package (e.g. conda files overwritten by pip)
+    ClobberedFiles,
+    /// Prints revisions of the environment (read from `conda-meta/history`) with packages they changed
+    History {
+        /// Prints how every revision changed leaves of the environment
+        #[structopt(short = "l", long)]
+        leaves: bool,
+    },
+    /// Prints conda packages that are not required by any package requested by the user (read from `conda-meta/history`)
+    Autoremove {
+        /// Name of the package that should be kept, together with its dependencies (can be used multiple times)
+        #[structopt(short = "k", long, number_of_values = 1)]
+        keep: Vec<String>,
+        /// Prints `conda remove` command that removes the packages
+        #[structopt(short = "c", long)]
+        command: bool,
+    },
+    /// Marks packages as installed manually (always leaves) or automatically (never leaves), like `apt-mark`
+    Mark {
+        /// Mark of the packages
+        #[structopt(possible_values = &["manual", "auto"])]
+        mark: Mark,
+        /// Names of the packages
+        #[structopt(required = true)]
+        packages: Vec<String>,
+    },
+    /// Prints packages explicitly requested by the user (read from `conda-meta/history`), together with their specs
+    Requested {
+        /// Prints where requested packages and leaves disagree
+        #[structopt(short = "c", long)]
+        compare: bool,
+    },
+    /// Verifies checksums and sizes of files installed by packages (all packages, if none is given)
+    Verify {
+        /// Names of the packages to verify
+        packages: Vec<String>,
     },
     /// Exports leaves to the file
     Export {
@@ -47,68 +141,291 @@ enum Commands {
             parse(from_os_str)
         )]
         filename: PathBuf,
+        /// Exports leaves of conda packages as they were right after given revision (see `history` command)
+        #[structopt(short = "r", long)]
+        revision: Option<usize>,
     },
 }
 
 fn main() -> io::Result<()> {
-    let opts: Opts = Opts::from_args();
+    // warnings (e.g. about metadata files that cannot be read) are printed unless `RUST_LOG` says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .format_timestamp(None)
+        .init();
 
-    match opts {
-        Opts {
-            no_pip: false,
-            commands,
-        } => match commands {
-            None => {
-                let leaves = get_leaves();
-                for leaf in leaves.iter() {
-                    println!("{}", leaf)
-                }
+    let Opts {
+        no_pip,
+        all,
+        include,
+        exclude,
+        pypi_mapping,
+        commands,
+    } = Opts::from_args();
+
+    // policy has to be set before any package is read
+    let policy = match all {
+        true => FilterPolicy::all(),
+        false => FilterPolicy::new(&include, &exclude).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2)
+        }),
+    };
+    set_filter_policy(policy);
+    if let Some(path) = pypi_mapping {
+        set_pypi_mapping(read_pypi_mapping(&path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2)
+        }));
+    }
+
+    match commands {
+        None => {
+            let leaves = get_leaves(no_pip);
+            for leaf in leaves.iter() {
+                println!("{}", leaf)
             }
-            Some(command) => match command {
-                Commands::Package {
-                    name,
-                    dependent_packages,
-                } => match dependent_packages {
-                    true => {
-                        if let Some(dep_packages) = get_dependent_packages(&name) {
-                            if dep_packages.len() == 0 {
+        }
+        Some(command) => match command {
+            Commands::Package {
+                name,
+                dependent_packages,
+                info,
+                extras,
+            } => match dependent_packages {
+                true => {
+                    if let Some(dep_packages) = get_dependent_packages(&name, no_pip) {
+                        // print the name of installed package, no matter how it was spelled
+                        let name = Metadata::from_name(&name).map_or(name, |m| m.name);
+                        if dep_packages.is_empty() {
+                            println!("{} is not required by any package in the environment", name)
+                        } else {
+                            println!("Following packages depend on {}:", name,)
+                        }
+                        for package in dep_packages {
+                            println!("- {}", package)
+                        }
+                    } else {
+                        eprintln!("Package '{}' not found", name);
+                        std::process::exit(404)
+                    }
+                }
+                false => match Metadata::from_name(name) {
+                    Ok(m) => {
+                        if extras {
+                            print_package_extras(&m);
+                            return Ok(());
+                        }
+                        let p: Package = m.into();
+                        if info {
+                            print_package_info(&p);
+                        } else {
+                            print_package(&p);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(404)
+                    }
+                },
+            },
+            Commands::Check => {
+                let unsatisfied = get_unsatisfied_dependencies();
+                let violated = get_violated_constraints();
+                if unsatisfied.is_empty() && violated.is_empty() {
+                    println!("All dependencies are satisfied");
+                } else {
+                    for u in unsatisfied.iter() {
+                        match &u.installed {
+                            Some(installed) => println!(
+                                "{} requires {}, but {} {} is installed",
+                                u.package, u.spec, u.dependency, installed
+                            ),
+                            None => {
                                 println!(
-                                    "{} is not required by any package in the environment",
-                                    name
+                                    "{} requires {}, but it's not installed",
+                                    u.package, u.spec
                                 )
-                            } else {
-                                println!("Following packages depend on {}:", name,)
                             }
-                            for package in dep_packages {
+                        }
+                    }
+                    for v in violated.iter() {
+                        println!(
+                            "{} constrains {}, but {} {} is installed",
+                            v.package, v.spec, v.constrained, v.installed
+                        )
+                    }
+                    std::process::exit(1)
+                }
+            }
+            Commands::Owner { path } => {
+                let PathOwners { path, owners } = get_path_owners(&path);
+                if owners.is_empty() {
+                    eprintln!("No package in the environment owns '{}'", path);
+                    std::process::exit(404)
+                }
+                for owner in owners.iter() {
+                    let metadata = Metadata::from_name(owner)?;
+                    println!(
+                        "{} is owned by {} (v{}), installed by {}",
+                        path,
+                        metadata.name,
+                        metadata.version,
+                        metadata.installer()
+                    )
+                }
+            }
+            Commands::OrphansFiles => {
+                let orphans = get_orphan_files(get_conda_prefix(), &FILE_OWNERS);
+                if orphans.is_empty() {
+                    println!("All files are owned by packages");
+                } else {
+                    println!("{}", orphan_files_to_lines(&orphans).join("\n"));
+                }
+            }
+            Commands::ClobberedFiles => {
+                let clobbered = get_clobbered_files(get_conda_prefix());
+                if clobbered.is_empty() {
+                    println!("No file is claimed by more than one package");
+                } else {
+                    println!("{}", clobbered_files_to_lines(&clobbered).join("\n"));
+                }
+            }
+            Commands::History { leaves } => match get_history() {
+                Ok(history) => {
+                    let history_leaves = match leaves {
+                        true => Some(get_history_leaves(&history)),
+                        false => None,
+                    };
+                    println!(
+                        "{}",
+                        history_to_lines(&history, history_leaves.as_deref()).join("\n")
+                    );
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(404)
+                }
+            },
+            Commands::Autoremove { keep, command } => {
+                match get_autoremovable_packages(&keep, no_pip) {
+                    Ok(packages) => {
+                        if packages.is_empty() {
+                            println!("All packages are required by requested packages");
+                        } else if command {
+                            println!("conda remove {}", packages.join(" "));
+                        } else {
+                            println!(
+                                "Following packages are not required by any requested package:"
+                            );
+                            for package in packages {
                                 println!("- {}", package)
                             }
-                        } else {
-                            eprintln!("Package '{}' not found", name);
-                            std::process::exit(404)
                         }
                     }
-                    false => match Metadata::from_name(name) {
-                        Ok(m) => {
-                            let p: Package = m.into();
-                            print_package(&p);
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(404)
+                    }
+                }
+            }
+            Commands::Mark { mark, packages } => match mark_packages(&packages, mark) {
+                Ok(names) => {
+                    let how = match mark {
+                        Mark::Manual => "manually",
+                        Mark::Auto => "automatically",
+                    };
+                    for name in names.iter() {
+                        println!("{} marked as {} installed", name, how)
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(match e.kind() {
+                        io::ErrorKind::NotFound => 404,
+                        _ => 1,
+                    })
+                }
+            },
+            Commands::Requested { compare } => match compare {
+                true => match get_disagreements(no_pip) {
+                    Ok(disagreements) => {
+                        if disagreements.is_empty() {
+                            println!("Requested packages and leaves agree");
                         }
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(404)
+                        for disagreement in disagreements.iter() {
+                            println!("{}", disagreement)
                         }
-                    },
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(404)
+                    }
                 },
-                Commands::Export { filename } => {
-                    let leaves = get_leaves();
-                    let env: CondaEnv = leaves.into();
-                    env.to_yml(&filename)?
+                false => match get_requested_packages() {
+                    Ok(requested) => {
+                        for r in requested.iter() {
+                            println!("{} ({})", r.name, r.spec)
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(404)
+                    }
+                },
+            },
+            Commands::Verify { packages } => match get_verification_problems(&packages) {
+                Ok(problems) => {
+                    if problems.is_empty() {
+                        println!("All files match their records");
+                    } else {
+                        for package in problems.iter() {
+                            let metadata = Metadata::from_name(&package.name)?;
+                            println!("{} (v{})", metadata.name, metadata.version);
+                            for file in package.files.iter() {
+                                println!("  {}: {}", file.path, file.problem)
+                            }
+                        }
+                        std::process::exit(1)
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(404)
                 }
             },
+            Commands::Export { filename, revision } => {
+                let env: CondaEnv = match revision {
+                    None => get_leaves(no_pip).into(),
+                    Some(revision) => {
+                        let history = get_history().unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(404)
+                        });
+                        let metadata =
+                            get_revision_metadata(&history, revision).unwrap_or_else(|| {
+                                eprintln!(
+                                    "Revision {} not found, the environment has {} revisions",
+                                    revision,
+                                    history.revisions.len()
+                                );
+                                std::process::exit(404)
+                            });
+                        // leaves of the revision are wrong, if dependencies of any package are unknown
+                        if !metadata.unresolved.is_empty() {
+                            eprintln!(
+                                "Cannot export revision {}, dependencies of {} are unknown",
+                                revision,
+                                metadata.unresolved.join(", ")
+                            );
+                            std::process::exit(404)
+                        }
+                        let leaves = get_revision_leaves(&metadata.packages);
+                        CondaEnv::from_revision(leaves, &metadata.packages)
+                    }
+                };
+                env.to_yml(&filename)?
+            }
         },
-        Opts { no_pip: true, .. } => {
-            eprintln!("Mixed conda-pip environments are not supported yet.");
-            std::process::exit(1)
-        }
     }
 
     Ok(())
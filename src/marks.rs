@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::Metadata;
+use crate::package::Installer;
+use crate::utils::{find_metadata, get_conda_meta_path, CONDA_METADATA};
+
+/// Name of the file in `conda-meta` directory, where marks of the environment are stored.
+/// It has no `.json` extension on purpose: conda reads every `conda-meta/*.json` file as a package record.
+pub const MARKS_FILENAME: &str = "conda-leaves-marks";
+
+/// Mark given by the user to an installed package, similar to `apt-mark`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mark {
+    /// Package installed on purpose: it's always a leaf and it's never reported by `autoremove`.
+    Manual,
+    /// Package installed only as a dependency: it's never a leaf and it's not kept by `autoremove`,
+    /// even if it was requested explicitly.
+    Auto,
+}
+
+impl FromStr for Mark {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manual" => Ok(Mark::Manual),
+            "auto" => Ok(Mark::Auto),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid mark '{}', expected 'manual' or 'auto'", s),
+            )),
+        }
+    }
+}
+
+/// Marks of packages of the environment, stored in `conda-meta/conda-leaves-marks` (json) file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Marks {
+    #[serde(default)]
+    manual: Vec<String>,
+    #[serde(default)]
+    auto: Vec<String>,
+}
+
+impl Marks {
+    /// Returns marks read from given json file, or no marks if the file doesn't exist.
+    ///
+    /// If the file cannot be read or has unexpected format, then an error is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let file = match File::open(path.as_ref()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let marks = serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid marks file '{}': {}", path.as_ref().display(), e),
+            )
+        })?;
+        Ok(marks)
+    }
+
+    /// Writes marks to given json file, overwriting it.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content + "\n")
+    }
+
+    /// Marks package with given name, replacing its previous mark.
+    pub fn mark<T: AsRef<str>>(&mut self, name: T, mark: Mark) {
+        let name = name.as_ref();
+        self.manual.retain(|n| n != name);
+        self.auto.retain(|n| n != name);
+        let names = match mark {
+            Mark::Manual => &mut self.manual,
+            Mark::Auto => &mut self.auto,
+        };
+        names.push(name.to_string());
+        names.sort();
+    }
+
+    /// Returns mark of the package with given name, if it has any.
+    pub fn get<T: AsRef<str>>(&self, name: T) -> Option<Mark> {
+        let name = name.as_ref();
+        if self.manual.iter().any(|n| n == name) {
+            Some(Mark::Manual)
+        } else if self.auto.iter().any(|n| n == name) {
+            Some(Mark::Auto)
+        } else {
+            None
+        }
+    }
+
+    /// Returns names of packages marked with given mark.
+    pub fn marked(&self, mark: Mark) -> &[String] {
+        match mark {
+            Mark::Manual => &self.manual,
+            Mark::Auto => &self.auto,
+        }
+    }
+}
+
+/// Returns marks of packages of the environment (see `Marks`), stored in `conda-meta` directory.
+///
+/// If the marks file cannot be read, a warning is printed and the environment is treated as having no marks
+/// (`mark` command still refuses to overwrite such file).
+pub fn get_marks() -> Marks {
+    read_marks(get_conda_meta_path().join(MARKS_FILENAME))
+}
+
+#[doc(hidden)]
+fn read_marks<P: AsRef<Path>>(path: P) -> Marks {
+    Marks::from_file(path).unwrap_or_else(|e| {
+        log::warn!("{}, marks are ignored", e);
+        Marks::default()
+    })
+}
+
+/// Marks given packages of the environment and stores the marks in `conda-meta` directory.
+/// Returns names of marked packages, as they are installed.
+///
+/// If any of the packages is not installed or the marks file cannot be read or written, then an error is returned.
+pub fn mark_packages(names: &[String], mark: Mark) -> Result<Vec<String>, io::Error> {
+    let names = names
+        .iter()
+        .map(|name| {
+            find_metadata(&CONDA_METADATA, name)
+                .map(|m| m.name.clone())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Package '{}' not found", name),
+                    )
+                })
+        })
+        .collect::<Result<Vec<String>, io::Error>>()?;
+    let path = get_conda_meta_path().join(MARKS_FILENAME);
+    let mut marks = Marks::from_file(&path)?;
+    for name in names.iter() {
+        marks.mark(name, mark);
+    }
+    marks.to_file(&path)?;
+    Ok(names)
+}
+
+#[doc(hidden)]
+/// Applies marks of the user to given leaves: installed packages marked as manually installed are leaves
+/// (even if other packages depend on them or they are filtered out by the policy),
+/// packages marked as automatically installed are not.
+pub(crate) fn apply_marks(
+    leaves: Vec<String>,
+    metadata: &HashMap<String, Metadata>,
+    no_pip: bool,
+    marks: &Marks,
+) -> Vec<String> {
+    let mut leaves: Vec<String> = leaves
+        .into_iter()
+        .filter(|name| marks.get(name) != Some(Mark::Auto))
+        .collect();
+    leaves.extend(
+        marks
+            .marked(Mark::Manual)
+            .iter()
+            .filter_map(|name| find_metadata(metadata, name))
+            .filter(|m| !(no_pip && m.installer() != Installer::Conda))
+            .map(|m| m.name.clone()),
+    );
+    leaves.sort();
+    leaves.dedup();
+    leaves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterPolicy;
+    use crate::utils::{get_prefix_metadata, leaves};
+
+    #[test]
+    fn test_mark_from_str() {
+        assert_eq!("manual".parse::<Mark>().unwrap(), Mark::Manual);
+        assert_eq!("auto".parse::<Mark>().unwrap(), Mark::Auto);
+        assert!("keep".parse::<Mark>().is_err())
+    }
+
+    #[test]
+    fn test_marks_mark() {
+        // given:
+        let mut marks = Marks::default();
+        // when:
+        marks.mark("ipykernel", Mark::Manual);
+        marks.mark("pytz", Mark::Manual);
+        marks.mark("pytz", Mark::Auto);
+        // then:
+        assert_eq!(marks.get("ipykernel"), Some(Mark::Manual));
+        assert_eq!(marks.get("pytz"), Some(Mark::Auto));
+        assert_eq!(marks.get("six"), None);
+        assert_eq!(marks.marked(Mark::Manual), ["ipykernel"])
+    }
+
+    #[test]
+    fn test_marks_from_missing_file() {
+        let marks = Marks::from_file("./tests/data/mixed-env/conda-meta/conda-leaves-marks");
+        assert_eq!(marks.unwrap(), Marks::default())
+    }
+
+    #[test]
+    fn test_marks_from_invalid_file() {
+        let marks = Marks::from_file("./tests/data/mixed-env/conda-meta/history");
+        assert_eq!(marks.unwrap_err().kind(), io::ErrorKind::InvalidData)
+    }
+
+    #[test]
+    fn test_marks_to_file() {
+        // given:
+        let path = std::env::temp_dir().join("conda-leaves-test-marks.json");
+        let mut marks = Marks::default();
+        marks.mark("ipykernel", Mark::Manual);
+        marks.mark("pytz", Mark::Auto);
+        // when:
+        marks.to_file(&path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let read_marks = Marks::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        // then:
+        assert_eq!(
+            content,
+            "{\n  \"manual\": [\n    \"ipykernel\"\n  ],\n  \"auto\": [\n    \"pytz\"\n  ]\n}\n"
+        );
+        assert_eq!(read_marks, marks)
+    }
+
+    #[test]
+    fn test_read_marks_invalid_file() {
+        // when:
+        let marks = read_marks("./tests/data/mixed-env/conda-meta/history");
+        // then:
+        assert_eq!(marks, Marks::default())
+    }
+
+    #[test]
+    fn test_apply_marks() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let mut marks = Marks::default();
+        marks.mark("six", Mark::Manual);
+        marks.mark("patsy", Mark::Auto);
+        marks.mark("pandas", Mark::Manual);
+        // when:
+        let all_leaves = apply_marks(
+            leaves(&metadata, false, &FilterPolicy::default()),
+            &metadata,
+            false,
+            &marks,
+        );
+        marks.mark("legacy-pkg", Mark::Manual);
+        let conda_leaves = apply_marks(
+            leaves(&metadata, true, &FilterPolicy::default()),
+            &metadata,
+            true,
+            &marks,
+        );
+        // then:
+        // `six` is required by `patsy`, `pandas` is not installed
+        assert_eq!(all_leaves, vec!["legacy-pkg", "six"]);
+        assert_eq!(conda_leaves, vec!["six"])
+    }
+}
@@ -0,0 +1,720 @@
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+
+use glob::Pattern;
+
+use crate::metadata::CondaRecord;
+use crate::version::{VersionOrder, VersionSpec};
+
+/// Platform subdirectories known by conda.
+/// They are used to split `channel/subdir::name` into channel and subdir.
+const KNOWN_SUBDIRS: [&str; 13] = [
+    "noarch",
+    "linux-32",
+    "linux-64",
+    "linux-aarch64",
+    "linux-armv6l",
+    "linux-armv7l",
+    "linux-ppc64le",
+    "linux-s390x",
+    "osx-64",
+    "osx-arm64",
+    "win-32",
+    "win-64",
+    "win-arm64",
+];
+
+/// Conda package specification, as written in `depends` and `constrains` sections
+/// of json metadata files, or in `conda-meta/history` file.
+///
+/// Following forms are supported:
+/// - `name`, `name 1.2.*`, `name 1.2 build*`, `name >=1,<2|3.*`
+/// - `name=1.2`, `name==1.2`, `name=1.2=build`, `name>=1.2`
+/// - `channel::name`, `channel/subdir::name`
+/// - `name[version='>=1.19',build=*mkl*]`
+///
+/// Version and build are kept as strings, exactly as conda normalizes them
+/// (e.g. `name=1.2` becomes version `1.2*`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchSpec {
+    pub name: String,
+    pub channel: Option<String>,
+    pub subdir: Option<String>,
+    pub version: Option<String>,
+    pub build: Option<String>,
+    pub build_number: Option<String>,
+    pub md5: Option<String>,
+    /// Original spec string.
+    pub spec: String,
+}
+
+impl MatchSpec {
+    /// Returns spec that matches any version and build of the package with given name,
+    /// keeping given original spec string (e.g. legacy `name (version)` form, that cannot be parsed).
+    pub fn name_only<T: AsRef<str>, U: AsRef<str>>(name: T, spec: U) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            channel: None,
+            subdir: None,
+            version: None,
+            build: None,
+            build_number: None,
+            md5: None,
+            spec: spec.as_ref().to_string(),
+        }
+    }
+
+    /// Returns new instance of MatchSpec from given spec string.
+    ///
+    /// If an invalid spec is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(spec: T) -> Result<Self, io::Error> {
+        let original = spec.as_ref().trim();
+        // comments are allowed in environment files and history
+        let mut spec_str = match original.find('#') {
+            Some(idx) => original[..idx].trim(),
+            None => original,
+        };
+        if spec_str.is_empty() {
+            return Err(invalid_spec(original, "empty spec"));
+        }
+
+        // 1. bracket section, e.g. `numpy[version='>=1.19',build=*mkl*]`
+        let mut brackets: Vec<(String, String)> = vec![];
+        if spec_str.ends_with(']') {
+            let open = match spec_str.find('[') {
+                Some(idx) => idx,
+                None => return Err(invalid_spec(original, "unbalanced brackets")),
+            };
+            brackets = parse_brackets(&spec_str[open + 1..spec_str.len() - 1])
+                .ok_or_else(|| invalid_spec(original, "invalid bracket section"))?;
+            spec_str = spec_str[..open].trim();
+        }
+        if spec_str.contains('[') || spec_str.contains(']') {
+            return Err(invalid_spec(original, "unbalanced brackets"));
+        }
+
+        // 2. channel and subdir, e.g. `conda-forge/linux-64::numpy`
+        let mut channel = None;
+        let mut subdir = None;
+        if let Some(idx) = spec_str.rfind("::") {
+            let (c, s) = split_channel(&spec_str[..idx]);
+            channel = c;
+            subdir = s;
+            spec_str = spec_str[idx + 2..].trim();
+        }
+
+        // 3. name, it's followed by whitespace or version operator
+        let name_end = spec_str
+            .find(|c: char| c.is_whitespace() || "=<>!~".contains(c))
+            .unwrap_or(spec_str.len());
+        let name = spec_str[..name_end].to_string();
+        let rest = spec_str[name_end..].trim();
+
+        // 4. version and build
+        let (mut version, mut build) = if rest.is_empty() {
+            (None, None)
+        } else {
+            let (version, build) = split_version_and_build(rest);
+            if version.ends_with(['<', '>', '=', '!', '~']) {
+                return Err(invalid_spec(original, "missing version"));
+            }
+            (Some(normalize_version(version, build.is_some())), build)
+        };
+
+        // 5. values from bracket section override everything else
+        let mut build_number = None;
+        let mut md5 = None;
+        for (key, value) in brackets {
+            match key.as_str() {
+                "version" => version = Some(value),
+                "build" => build = Some(value),
+                "build_number" => build_number = Some(value),
+                "channel" => {
+                    let (c, s) = split_channel(&value);
+                    channel = c;
+                    subdir = s.or(subdir);
+                }
+                "subdir" => subdir = Some(value),
+                "md5" => md5 = Some(value),
+                // other keys (e.g. `license` or `features`) are not used by conda-leaves
+                _ => (),
+            }
+        }
+
+        if name.is_empty() {
+            return Err(invalid_spec(original, "missing package name"));
+        }
+
+        Ok(Self {
+            name,
+            channel,
+            subdir,
+            version,
+            build,
+            build_number,
+            md5,
+            spec: original.to_string(),
+        })
+    }
+
+    /// Returns `true` if given conda record satisfies the spec.
+    ///
+    /// Name, version, build, build number, channel, subdir and md5 are taken into account.
+    /// Spec with invalid version constraint doesn't match any record.
+    pub fn matches(&self, record: &CondaRecord) -> bool {
+        if self.name != "*" && self.name != record.name {
+            return false;
+        }
+        if let Some(version) = &self.version {
+            let matched = match (
+                VersionSpec::parse(version),
+                VersionOrder::parse(&record.version),
+            ) {
+                (Ok(spec), Ok(version)) => spec.matches(&version),
+                _ => false,
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(build) = &self.build {
+            let matched = Pattern::new(build)
+                .map(|p| p.matches(&record.build))
+                .unwrap_or(false);
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(build_number) = &self.build_number {
+            if !build_number_matches(build_number, record.build_number) {
+                return false;
+            }
+        }
+        if let Some(channel) = &self.channel {
+            match &record.channel {
+                Some(record_channel) if channel_matches(channel, record_channel) => (),
+                _ => return false,
+            }
+        }
+        if self.subdir.is_some() && self.subdir != record.subdir {
+            return false;
+        }
+        if self.md5.is_some() && self.md5 != record.md5 {
+            return false;
+        }
+        true
+    }
+
+    /// Returns version and build constraints of the spec joined with space.
+    /// If spec doesn't define any constraint, `None` is returned.
+    pub fn constraint(&self) -> Option<String> {
+        match (&self.version, &self.build) {
+            (Some(version), Some(build)) => Some(format!("{} {}", version, build)),
+            (Some(version), None) => Some(version.to_string()),
+            (None, Some(build)) => Some(format!("* {}", build)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl FromStr for MatchSpec {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MatchSpec::parse(s)
+    }
+}
+
+/// Formats MatchSpec in its canonical form, e.g. `conda-forge/linux-64::numpy >=1.19 py38*`.
+impl fmt::Display for MatchSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match (&self.channel, &self.subdir) {
+            (Some(channel), Some(subdir)) => write!(f, "{}/{}::", channel, subdir)?,
+            (Some(channel), None) => write!(f, "{}::", channel)?,
+            (None, _) => (),
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(constraint) = self.constraint() {
+            write!(f, " {}", constraint)?;
+        }
+        let brackets: Vec<String> = [("build_number", &self.build_number), ("md5", &self.md5)]
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|v| format!("{}='{}'", key, v)))
+            .collect();
+        if !brackets.is_empty() {
+            write!(f, "[{}]", brackets.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+fn invalid_spec(spec: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid MatchSpec '{}': {}", spec, reason),
+    )
+}
+
+#[doc(hidden)]
+/// Splits channel string into channel and optional subdir.
+/// Subdir is recognized only if it's one of the platforms known by conda.
+fn split_channel(channel: &str) -> (Option<String>, Option<String>) {
+    let channel = channel.trim().trim_end_matches('/');
+    if channel.is_empty() || channel == "*" {
+        return (None, None);
+    }
+    match channel.rsplit_once('/') {
+        Some((c, s)) if KNOWN_SUBDIRS.contains(&s) => (Some(c.to_string()), Some(s.to_string())),
+        _ => (Some(channel.to_string()), None),
+    }
+}
+
+#[doc(hidden)]
+/// Returns `true` if channel of the record (short name or full url) is the same as the given channel.
+fn channel_matches(channel: &str, record_channel: &str) -> bool {
+    let normalize = |c: &str| -> String {
+        let c = c.trim_end_matches('/');
+        match c.rsplit_once('/') {
+            Some((c, s)) if KNOWN_SUBDIRS.contains(&s) => c.to_string(),
+            _ => c.to_string(),
+        }
+    };
+    let channel = normalize(channel);
+    let record_channel = normalize(record_channel);
+    record_channel == channel || record_channel.ends_with(&format!("/{}", channel))
+}
+
+#[doc(hidden)]
+/// Returns `true` if build number satisfies given constraint, e.g. `3` or `>=3`.
+fn build_number_matches(constraint: &str, build_number: u64) -> bool {
+    let constraint = constraint.trim();
+    let operators = ["==", "!=", "<=", ">=", "<", ">", "="];
+    let (operator, number) = operators
+        .iter()
+        .find_map(|op| constraint.strip_prefix(op).map(|n| (*op, n)))
+        .unwrap_or(("==", constraint));
+    let number = match number.trim().parse::<u64>() {
+        Ok(number) => number,
+        Err(_) => return false,
+    };
+    match operator {
+        "!=" => build_number != number,
+        "<=" => build_number <= number,
+        ">=" => build_number >= number,
+        "<" => build_number < number,
+        ">" => build_number > number,
+        _ => build_number == number,
+    }
+}
+
+#[doc(hidden)]
+/// Parses content of the bracket section into the list of `(key, value)` pairs.
+/// Values may be quoted with single or double quotes, which allows them to contain commas.
+fn parse_brackets(content: &str) -> Option<Vec<(String, String)>> {
+    let mut pairs = vec![];
+    let mut chars = content.chars().peekable();
+    loop {
+        // skip separators
+        while let Some(&c) = chars.peek() {
+            if c == ',' || c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        for c in chars.by_ref() {
+            if c == '=' {
+                break;
+            }
+            key.push(c);
+        }
+        let key = key.trim().to_string();
+        if key.is_empty() {
+            return None;
+        }
+        let mut value = String::new();
+        match chars.peek() {
+            Some(&quote) if quote == '\'' || quote == '"' => {
+                chars.next();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == quote {
+                        closed = true;
+                        break;
+                    }
+                    value.push(c);
+                }
+                if !closed {
+                    return None;
+                }
+            }
+            _ => {
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+        }
+        if value.is_empty() {
+            return None;
+        }
+        pairs.push((key, value));
+    }
+    Some(pairs)
+}
+
+#[doc(hidden)]
+/// Splits version and build part of the spec, e.g.:
+/// - `1.2.3 py38_0` -> (`1.2.3`, `py38_0`)
+/// - `=1.8=py27_0` -> (`=1.8`, `py27_0`)
+/// - `>=1.0 , < 2.0 py34_0` -> (`>=1.0,<2.0`, `py34_0`)
+/// - `>1.8,<2|==1.7` -> (`>1.8,<2|==1.7`, None)
+///
+/// Build is separated from the version by space or `=`, which can't follow any version operator.
+/// Build itself can't contain any of version operators.
+fn split_version_and_build(spec: &str) -> (String, Option<String>) {
+    let chars: Vec<(usize, char)> = spec.char_indices().collect();
+    for (pos, &(idx, c)) in chars.iter().enumerate().skip(1) {
+        if c != ' ' && c != '=' {
+            continue;
+        }
+        let prev = chars[pos - 1].1;
+        if "=!|,<>~".contains(prev) {
+            continue;
+        }
+        let build = &spec[idx + 1..];
+        if build.is_empty() || build.contains(|c: char| "-=,|<>~".contains(c)) {
+            continue;
+        }
+        let version = spec[..idx].replace(' ', "");
+        let build = build.trim();
+        let build = if build.is_empty() {
+            None
+        } else {
+            Some(build.to_string())
+        };
+        return (version, build);
+    }
+    (spec.replace(' ', ""), None)
+}
+
+#[doc(hidden)]
+/// Translates conda shorthand versions:
+/// - `==1.2.3` -> `1.2.3` (exact match)
+/// - `=1.2.3` -> `1.2.3*` (prefix match), unless build is given
+fn normalize_version(version: String, has_build: bool) -> String {
+    if let Some(exact) = version.strip_prefix("==") {
+        if !has_build && !exact.contains(|c: char| "=,|".contains(c)) {
+            return exact.to_string();
+        }
+        return version;
+    }
+    if let Some(fuzzy) = version.strip_prefix('=') {
+        if fuzzy.contains(|c: char| "=,|".contains(c)) {
+            return version;
+        }
+        if has_build || fuzzy.ends_with('*') {
+            return fuzzy.to_string();
+        }
+        return format!("{}*", fuzzy);
+    }
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Real-world specs taken from `depends`, `constrains` and `conda-meta/history` files,
+    /// with expected (channel, subdir, name, version, build).
+    #[allow(clippy::type_complexity)]
+    const CORPUS: [(
+        &str,
+        (Option<&str>, Option<&str>, &str, Option<&str>, Option<&str>),
+    ); 38] = [
+        ("numpy", (None, None, "numpy", None, None)),
+        (
+            "ca-certificates",
+            (None, None, "ca-certificates", None, None),
+        ),
+        (
+            "_libgcc_mutex 0.1 main",
+            (None, None, "_libgcc_mutex", Some("0.1"), Some("main")),
+        ),
+        (
+            "_openmp_mutex >=4.5",
+            (None, None, "_openmp_mutex", Some(">=4.5"), None),
+        ),
+        (
+            "__glibc >=2.17,<3.0.a0",
+            (None, None, "__glibc", Some(">=2.17,<3.0.a0"), None),
+        ),
+        (
+            "libgcc-ng >=7.3.0",
+            (None, None, "libgcc-ng", Some(">=7.3.0"), None),
+        ),
+        (
+            "numpy >=1.19,<2.0a0",
+            (None, None, "numpy", Some(">=1.19,<2.0a0"), None),
+        ),
+        (
+            "openssl >=1.1.1g,<1.1.2a",
+            (None, None, "openssl", Some(">=1.1.1g,<1.1.2a"), None),
+        ),
+        (
+            "python >=3.8,<3.9.0a0",
+            (None, None, "python", Some(">=3.8,<3.9.0a0"), None),
+        ),
+        (
+            "python_abi 3.8.* *_cp38",
+            (None, None, "python_abi", Some("3.8.*"), Some("*_cp38")),
+        ),
+        ("blas * mkl", (None, None, "blas", Some("*"), Some("mkl"))),
+        (
+            "mkl 2020.2 256",
+            (None, None, "mkl", Some("2020.2"), Some("256")),
+        ),
+        ("numpy 1.19.*", (None, None, "numpy", Some("1.19.*"), None)),
+        (
+            "numpy 1.19.* py38*",
+            (None, None, "numpy", Some("1.19.*"), Some("py38*")),
+        ),
+        (
+            "numpy >=1.0 , < 2.0 py34_0",
+            (None, None, "numpy", Some(">=1.0,<2.0"), Some("py34_0")),
+        ),
+        (
+            "numpy ==1.11.3 py36_0",
+            (None, None, "numpy", Some("==1.11.3"), Some("py36_0")),
+        ),
+        ("numpy >= 1.19", (None, None, "numpy", Some(">=1.19"), None)),
+        (
+            "numpy >=1,<2|3.*",
+            (None, None, "numpy", Some(">=1,<2|3.*"), None),
+        ),
+        (
+            "numpy >1.8,<2|==1.7",
+            (None, None, "numpy", Some(">1.8,<2|==1.7"), None),
+        ),
+        ("numpy=1.19", (None, None, "numpy", Some("1.19*"), None)),
+        ("numpy=1.19.*", (None, None, "numpy", Some("1.19.*"), None)),
+        ("numpy==1.19.1", (None, None, "numpy", Some("1.19.1"), None)),
+        ("numpy>=1.19", (None, None, "numpy", Some(">=1.19"), None)),
+        (
+            "numpy!=1.19.0",
+            (None, None, "numpy", Some("!=1.19.0"), None),
+        ),
+        (
+            "numpy~=1.19.0",
+            (None, None, "numpy", Some("~=1.19.0"), None),
+        ),
+        (
+            "numpy=1.8=py27_0",
+            (None, None, "numpy", Some("1.8"), Some("py27_0")),
+        ),
+        (
+            "pytorch=1.7.1=py3.8_cuda11.0.221_cudnn8.0.5_0",
+            (
+                None,
+                None,
+                "pytorch",
+                Some("1.7.1"),
+                Some("py3.8_cuda11.0.221_cudnn8.0.5_0"),
+            ),
+        ),
+        (
+            "conda-forge::numpy",
+            (Some("conda-forge"), None, "numpy", None, None),
+        ),
+        (
+            "conda-forge::numpy==1.19.1",
+            (Some("conda-forge"), None, "numpy", Some("1.19.1"), None),
+        ),
+        (
+            "pkgs/main::python=3.8",
+            (Some("pkgs/main"), None, "python", Some("3.8*"), None),
+        ),
+        (
+            "pkgs/main/linux-64::numpy-base=1.19.1=py38hfa32c7d_0",
+            (
+                Some("pkgs/main"),
+                Some("linux-64"),
+                "numpy-base",
+                Some("1.19.1"),
+                Some("py38hfa32c7d_0"),
+            ),
+        ),
+        (
+            "conda-forge/noarch::six",
+            (Some("conda-forge"), Some("noarch"), "six", None, None),
+        ),
+        (
+            "numpy[version='>=1.19',build=*mkl*]",
+            (None, None, "numpy", Some(">=1.19"), Some("*mkl*")),
+        ),
+        (
+            "numpy[version=\">=1.19,<2\"]",
+            (None, None, "numpy", Some(">=1.19,<2"), None),
+        ),
+        (
+            "conda-forge::numpy[version='>=1.19', build=*mkl*]",
+            (
+                Some("conda-forge"),
+                None,
+                "numpy",
+                Some(">=1.19"),
+                Some("*mkl*"),
+            ),
+        ),
+        (
+            "numpy 1.19.*[build=py38*]",
+            (None, None, "numpy", Some("1.19.*"), Some("py38*")),
+        ),
+        (
+            "numpy[channel=conda-forge/linux-64]",
+            (Some("conda-forge"), Some("linux-64"), "numpy", None, None),
+        ),
+        (
+            "r-base >=3.6,<3.7.0a0  # comment",
+            (None, None, "r-base", Some(">=3.6,<3.7.0a0"), None),
+        ),
+    ];
+
+    #[test]
+    fn test_parse_corpus() {
+        for (spec, (channel, subdir, name, version, build)) in CORPUS.iter() {
+            // when:
+            let m = MatchSpec::parse(spec).unwrap();
+            // then:
+            assert_eq!(m.channel.as_deref(), *channel, "channel of '{}'", spec);
+            assert_eq!(m.subdir.as_deref(), *subdir, "subdir of '{}'", spec);
+            assert_eq!(m.name, *name, "name of '{}'", spec);
+            assert_eq!(m.version.as_deref(), *version, "version of '{}'", spec);
+            assert_eq!(m.build.as_deref(), *build, "build of '{}'", spec);
+        }
+    }
+
+    #[test]
+    fn test_parse_keeps_original_spec() {
+        let m = MatchSpec::parse(" numpy >=1.19,<2.0a0 ").unwrap();
+        assert_eq!(m.spec, String::from("numpy >=1.19,<2.0a0"))
+    }
+
+    #[test]
+    fn test_parse_build_number_and_md5() {
+        // given:
+        let spec = "numpy[build_number='>=3', md5=abc123]";
+        // when:
+        let m = MatchSpec::parse(spec).unwrap();
+        // then:
+        assert_eq!(m.build_number, Some(String::from(">=3")));
+        assert_eq!(m.md5, Some(String::from("abc123")))
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let invalid_specs = vec![
+            "",
+            "   ",
+            "numpy==",
+            "numpy>=",
+            "numpy[version='>=1.19'",
+            "numpy[version]",
+            "numpy]",
+            "[version=1.0]",
+            ">=1.0",
+        ];
+        for spec in invalid_specs {
+            assert!(
+                MatchSpec::parse(spec).is_err(),
+                "'{}' should be invalid",
+                spec
+            )
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let m: MatchSpec = "numpy 1.19.*".parse().unwrap();
+        assert_eq!(m.name, String::from("numpy"))
+    }
+
+    #[test]
+    fn test_constraint() {
+        let constraint = |spec: &str| MatchSpec::parse(spec).unwrap().constraint();
+        assert_eq!(constraint("numpy"), None);
+        assert_eq!(constraint("numpy >=1.19"), Some(String::from(">=1.19")));
+        assert_eq!(
+            constraint("mkl 2020.2 256"),
+            Some(String::from("2020.2 256"))
+        );
+        assert_eq!(
+            constraint("numpy[build=*mkl*]"),
+            Some(String::from("* *mkl*"))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let display = |spec: &str| MatchSpec::parse(spec).unwrap().to_string();
+        assert_eq!(display("numpy"), "numpy");
+        assert_eq!(display("numpy=1.8=py27_0"), "numpy 1.8 py27_0");
+        assert_eq!(
+            display("pkgs/main/linux-64::numpy>=1.19"),
+            "pkgs/main/linux-64::numpy >=1.19"
+        );
+        assert_eq!(display("numpy[build_number=3]"), "numpy[build_number='3']");
+    }
+
+    #[test]
+    fn test_matches() {
+        // given:
+        let record = CondaRecord {
+            name: String::from("numpy"),
+            version: String::from("1.19.1"),
+            build: String::from("py38_mkl_0"),
+            build_number: 3,
+            channel: Some(String::from(
+                "https://conda.anaconda.org/conda-forge/linux-64",
+            )),
+            subdir: Some(String::from("linux-64")),
+            ..Default::default()
+        };
+        let cases = vec![
+            ("numpy", true),
+            ("scipy", false),
+            ("numpy >=1.19,<2.0a0", true),
+            ("numpy >=1.20", false),
+            ("numpy 1.19.*", true),
+            ("numpy=1.19", true),
+            ("numpy==1.19", false),
+            ("numpy 1.19.1 py38*", true),
+            ("numpy 1.19.1 py37*", false),
+            ("numpy[version='>=1.19',build=*mkl*]", true),
+            ("numpy[build=*openblas*]", false),
+            ("numpy[build_number='>=3']", true),
+            ("numpy[build_number=2]", false),
+            ("conda-forge::numpy", true),
+            ("defaults::numpy", false),
+            ("conda-forge/linux-64::numpy", true),
+            ("conda-forge/osx-64::numpy", false),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(
+                MatchSpec::parse(spec).unwrap().matches(&record),
+                expected,
+                "'{}' matches record",
+                spec
+            )
+        }
+    }
+}
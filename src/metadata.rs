@@ -2,29 +2,257 @@ use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, ErrorKind};
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::de;
 use serde::{Deserialize, Deserializer};
 
-use crate::utils::{split_and_take_n_elem, CONDA_METADATA};
+use crate::filter::get_filter_policy;
+use crate::history::HistoryPackage;
+use crate::matchspec::MatchSpec;
+use crate::package::Installer;
+use crate::pep508::{Marker, MarkerOperator, MarkerValue, Requirement};
+use crate::utils::{canonicalize_name, find_metadata, CONDA_METADATA, MARKER_ENVIRONMENT};
 
-// TODO I may want to consider adding `metadata_version` field
-// I assume compatibility with PEP 566 - Metadata v2.1
-// https://www.python.org/dev/peps/pep-0566/
 /// Core primitive of the library that represents Python package metadata.
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
 pub struct Metadata {
     pub name: String,
     pub version: String,
-    #[serde(
-        rename(deserialize = "depends"),
-        deserialize_with = "string_or_seq_string"
-    )]
-    pub requires_dist: Vec<String>,
+    /// Dependencies of conda package, taken from `depends` section of json metadata file.
+    #[serde(default, deserialize_with = "string_or_seq_string")]
+    pub depends: Vec<MatchSpec>,
+    /// Dependencies of Python package, taken from `Requires-Dist` lines of METADATA file.
+    #[serde(skip)]
+    pub requires_dist: Vec<Requirement>,
+    /// Extras declared by Python package, taken from `Provides-Extra` lines of METADATA file.
+    #[serde(skip)]
+    pub provides_extra: Vec<String>,
+    /// Descriptive fields of Python package, available only for packages read from METADATA or PKG-INFO file.
+    #[serde(skip)]
+    pub core_metadata: Option<CoreMetadata>,
+    /// Name of the tool that installed Python package, taken from `INSTALLER` file of dist-info.
+    #[serde(skip)]
+    pub installed_by: Option<String>,
+    /// Origin of Python package (PEP 610), taken from `direct_url.json` file of dist-info.
+    #[serde(skip)]
+    pub direct_url: Option<DirectUrl>,
+    /// Files installed by Python package, taken from `RECORD` file of dist-info (or `installed-files.txt` of egg-info),
+    /// together with files of the dist-info (egg-info) itself.
+    #[serde(skip)]
+    pub record_files: Vec<RecordFile>,
+    /// Directory Python package was installed into (usually `site-packages`),
+    /// paths of `record_files` are relative to it.
+    #[serde(skip)]
+    pub location: Option<PathBuf>,
+    /// Full conda record, available only for packages read from `conda-meta`.
+    #[serde(skip)]
+    pub record: Option<CondaRecord>,
+    /// PEP 503 canonical names of Python distributions provided by conda package (e.g. `torch` for `pytorch`),
+    /// inferred from `*.dist-info` and `*.egg-info` directories the package ships, unless overridden by the user.
+    #[serde(skip)]
+    pub pypi_names: Vec<String>,
+}
+
+/// Descriptive fields of Python core metadata (METADATA or PKG-INFO file),
+/// that are not needed to resolve dependencies of the package.
+///
+/// https://packaging.python.org/specifications/core-metadata/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoreMetadata {
+    pub metadata_version: Option<String>,
+    pub summary: Option<String>,
+    pub home_page: Option<String>,
+    pub license: Option<String>,
+    pub classifiers: Vec<String>,
+    pub requires_python: Option<String>,
+}
+
+/// Typed representation of the json record that conda keeps for every package in `conda-meta` folder.
+///
+/// Only `name` and `version` are mandatory, every other field is optional,
+/// because older conda versions didn't write all of them.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct CondaRecord {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub build: String,
+    #[serde(default)]
+    pub build_number: u64,
+    pub channel: Option<String>,
+    pub subdir: Option<String>,
+    pub url: Option<String>,
+    #[serde(rename = "fn")]
+    pub filename: Option<String>,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+    pub timestamp: Option<u64>,
+    pub license: Option<String>,
+    pub requested_spec: Option<String>,
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Optional dependencies: packages that don't have to be installed,
+    /// but if they are, they have to satisfy given spec (e.g. `pyqt >=5.12`).
+    #[serde(default)]
+    pub constrains: Vec<String>,
+    /// Paths of files installed by the package, relative to the environment prefix.
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Details (checksums, sizes) of files installed by the package, written by newer conda versions.
+    pub paths_data: Option<PathsData>,
+}
+
+impl CondaRecord {
+    /// Returns paths of files installed by the package (relative to the environment prefix),
+    /// taken from `paths_data` if available, or from `files` otherwise.
+    pub fn installed_paths(&self) -> Vec<&str> {
+        match &self.paths_data {
+            Some(paths_data) => paths_data.paths.iter().map(|p| p.path.as_str()).collect(),
+            None => self.files.iter().map(|f| f.as_str()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct PathsData {
+    #[serde(default)]
+    pub paths: Vec<PathData>,
+    #[serde(default)]
+    pub paths_version: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct PathData {
+    #[serde(rename = "_path")]
+    pub path: String,
+    #[serde(default)]
+    pub path_type: String,
+    pub sha256: Option<String>,
+    pub size_in_bytes: Option<u64>,
+    /// Placeholder replaced by the environment prefix during installation (the file differs from the one in the package).
+    pub prefix_placeholder: Option<String>,
+    /// Checksum of the file after the placeholder was replaced, written by newer conda versions.
+    pub sha256_in_prefix: Option<String>,
+}
+
+/// Single entry of `RECORD` file of dist-info, e.g. `six.py,sha256=<urlsafe-base64>,34549`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordFile {
+    /// Path relative to the directory package was installed into.
+    pub path: String,
+    /// Hash in `<algorithm>=<urlsafe-base64-digest>` form.
+    pub hash: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// Typed representation of PEP 610 `direct_url.json` file,
+/// which is written for distributions installed from URL, VCS or local directory.
+///
+/// https://packaging.python.org/specifications/direct-url/
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct DirectUrl {
+    pub url: String,
+    pub vcs_info: Option<VcsInfo>,
+    pub dir_info: Option<DirInfo>,
+    pub archive_info: Option<ArchiveInfo>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct VcsInfo {
+    pub vcs: String,
+    pub commit_id: String,
+    pub requested_revision: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct DirInfo {
+    #[serde(default)]
+    pub editable: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct ArchiveInfo {
+    pub hash: Option<String>,
+}
+
+impl DirectUrl {
+    /// Returns `true` if distribution was installed in editable (development) mode.
+    pub fn is_editable(&self) -> bool {
+        self.dir_info.as_ref().map(|d| d.editable).unwrap_or(false)
+    }
 }
 
 impl Metadata {
+    /// Returns installer of the package.
+    /// Packages without conda record have been found in `site-packages` only,
+    /// so they were installed by the tool named in their `INSTALLER` file (pip if it's missing).
+    pub(crate) fn installer(&self) -> Installer {
+        match (&self.record, &self.installed_by) {
+            (Some(_), _) => Installer::Conda,
+            (None, Some(installed_by)) => Installer::from(installed_by.as_str()),
+            (None, None) => Installer::Pip,
+        }
+    }
+
+    /// Returns names of all dependencies of the package,
+    /// no matter if they come from conda `depends` or Python `Requires-Dist`.
+    pub fn dependency_names(&self) -> Vec<&str> {
+        self.depends
+            .iter()
+            .map(|d| d.name.as_str())
+            .chain(self.applicable_requires_dist().map(|r| r.name.as_str()))
+            .collect()
+    }
+
+    /// Returns all dependencies of conda package, taken from `depends` field of conda record,
+    /// including packages filtered out by the policy (see `FilterPolicy`), which only affects what is displayed.
+    /// Specs that cannot be parsed are kept by package name only.
+    pub fn record_depends(&self) -> Vec<MatchSpec> {
+        match &self.record {
+            Some(record) => record
+                .depends
+                .iter()
+                .filter_map(|d| parse_depends(d))
+                .collect(),
+            None => self.depends.clone(),
+        }
+    }
+
+    /// Returns constraints (`constrains` field of conda record) the package puts on other packages,
+    /// that are applied only if those packages are installed. Specs that cannot be parsed are skipped.
+    pub fn constrains(&self) -> Vec<MatchSpec> {
+        self.record
+            .iter()
+            .flat_map(|r| r.constrains.iter())
+            .filter_map(|c| MatchSpec::parse(c).ok())
+            .collect()
+    }
+
+    /// Returns `Requires-Dist` requirements whose environment markers hold in the inspected environment.
+    pub fn applicable_requires_dist(&self) -> impl Iterator<Item = &Requirement> {
+        self.requires_dist.iter().filter(|r| match &r.marker {
+            Some(marker) => marker.evaluate(&MARKER_ENVIRONMENT),
+            None => true,
+        })
+    }
+
+    /// Returns requirements that are pulled in only by given extra,
+    /// e.g. `extra_requires_dist("complete")` returns what `dask[complete]` adds on top of `dask`.
+    pub fn extra_requires_dist<T: AsRef<str>>(&self, extra: T) -> Vec<&Requirement> {
+        let environment = MARKER_ENVIRONMENT.with_extra(extra);
+        self.requires_dist
+            .iter()
+            .filter(|r| match &r.marker {
+                Some(marker) => {
+                    marker.evaluate(&environment) && !marker.evaluate(&MARKER_ENVIRONMENT)
+                }
+                None => false,
+            })
+            .collect()
+    }
+
     /// Returns new instance of Metadata from given Metadata file path.    
     /// It's able to parse files such as PKG-INFO and METADATA.
     ///
@@ -33,40 +261,145 @@ impl Metadata {
     where
         P: 'a + ?Sized + AsRef<Path>,
     {
-        let mut name: String = String::new();
-        let mut version: String = String::new();
-        let mut requires_dist: Vec<String> = vec![];
-
         let input = fs::File::open(path)?;
-        let buffered = BufReader::new(input);
-
-        for line in buffered.lines().map(|l| l.unwrap()) {
-            if line.starts_with("Name") {
-                let package_name = split_and_take_n_elem(&line, 1).unwrap();
-                name.push_str(package_name);
-            } else if line.starts_with("Version") {
-                let package_version = split_and_take_n_elem(&line, 1).unwrap();
-                version.push_str(package_version);
-            } else if line.starts_with("Provides-Extra") {
-                // I care only about default requirements,
-                // so if I find this line I quit
-                break;
-            } else if line.starts_with("Requires-Dist") {
-                let dependency_name = split_and_take_n_elem(&line, 1).unwrap();
-                requires_dist.push(dependency_name.to_string())
+        let headers = read_headers(BufReader::new(input))?;
+
+        let mut name: Option<String> = None;
+        let mut version: Option<String> = None;
+        let mut requires_dist: Vec<Requirement> = vec![];
+        let mut provides_extra: Vec<String> = vec![];
+        let mut core_metadata = CoreMetadata::default();
+
+        for (key, value) in headers {
+            // field names are case-insensitive
+            match key.to_lowercase().as_str() {
+                "name" => name = Some(value),
+                "version" => version = Some(value),
+                // a single malformed requirement doesn't make the whole distribution unreadable
+                "requires-dist" => match Requirement::parse(&value) {
+                    Ok(requirement) => requires_dist.push(requirement),
+                    Err(e) => log::warn!("{} in '{}', skipped", e, path.as_ref().display()),
+                },
+                "provides-extra" => provides_extra.push(value),
+                "metadata-version" => core_metadata.metadata_version = Some(value),
+                "summary" => core_metadata.summary = Some(value),
+                "home-page" => core_metadata.home_page = Some(value),
+                "license" => core_metadata.license = Some(value),
+                "classifier" => core_metadata.classifiers.push(value),
+                "requires-python" => core_metadata.requires_python = Some(value),
+                _ => {}
             }
         }
 
+        let missing_field = |field: &str| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Missing '{}' field in '{}'", field, path.as_ref().display()),
+            )
+        };
         let metadata = Self {
-            name,
-            version,
+            name: name.ok_or_else(|| missing_field("Name"))?,
+            version: version.ok_or_else(|| missing_field("Version"))?,
+            depends: vec![],
             requires_dist,
+            provides_extra,
+            core_metadata: Some(core_metadata),
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         Ok(metadata)
     }
 
+    /// Returns new instance of Metadata from given `.dist-info` directory path.
+    /// Besides METADATA file, it reads `INSTALLER` and `direct_url.json` files, if they exist.
+    ///
+    /// If an invalid path is given, then an error is returned.
+    pub fn from_dist_info<'a, P>(path: &'a P) -> Result<Self, io::Error>
+    where
+        P: 'a + ?Sized + AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut metadata = Metadata::from_metadata_file(&path.join("METADATA"))?;
+        let installer_path = path.join("INSTALLER");
+        if installer_path.is_file() {
+            metadata.installed_by = Some(fs::read_to_string(installer_path)?.trim().to_string());
+        }
+        let direct_url_path = path.join("direct_url.json");
+        if direct_url_path.is_file() {
+            let reader = BufReader::new(File::open(direct_url_path)?);
+            metadata.direct_url = Some(serde_json::from_reader(reader)?);
+        }
+        let record_path = path.join("RECORD");
+        if record_path.is_file() {
+            metadata.record_files = read_record(BufReader::new(File::open(record_path)?))?;
+        }
+        add_info_dir_files(path, &mut metadata.record_files)?;
+        metadata.location = path.parent().map(|p| p.to_path_buf());
+        Ok(metadata)
+    }
+
+    /// Returns new instance of Metadata from given `.egg-info` path of legacy (setuptools) distribution.
+    /// `.egg-info` is either a single PKG-INFO file or a directory,
+    /// which keeps dependencies in `requires.txt` rather than in its PKG-INFO file
+    /// and files installed by the package in `installed-files.txt` (relative to the directory).
+    ///
+    /// If an invalid path is given, then an error is returned.
+    pub fn from_egg_info<'a, P>(path: &'a P) -> Result<Self, io::Error>
+    where
+        P: 'a + ?Sized + AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let info_dir = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if path.is_file() {
+            let mut metadata = Metadata::from_metadata_file(path)?;
+            metadata.record_files = vec![RecordFile {
+                path: info_dir,
+                ..Default::default()
+            }];
+            metadata.location = path.parent().map(|p| p.to_path_buf());
+            return Ok(metadata);
+        }
+        let mut metadata = Metadata::from_metadata_file(&path.join("PKG-INFO"))?;
+        let installed_files_path = path.join("installed-files.txt");
+        if installed_files_path.is_file() {
+            metadata.record_files = fs::read_to_string(installed_files_path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| RecordFile {
+                    path: format!("{}/{}", info_dir, line.trim()),
+                    ..Default::default()
+                })
+                .collect();
+        }
+        add_info_dir_files(path, &mut metadata.record_files)?;
+        metadata.location = path.parent().map(|p| p.to_path_buf());
+        let requires_path = path.join("requires.txt");
+        if requires_path.is_file() {
+            let requires = read_requires_txt(BufReader::new(File::open(requires_path)?))?;
+            for requirement in requires {
+                if !metadata.requires_dist.contains(&requirement) {
+                    metadata.requires_dist.push(requirement)
+                }
+            }
+            for extra in requires_txt_extras(&metadata.requires_dist) {
+                if !metadata.provides_extra.contains(&extra) {
+                    metadata.provides_extra.push(extra)
+                }
+            }
+        }
+        Ok(metadata)
+    }
+
     /// Returns new instance of Metadata from given json metadata file path
     /// (conda keeps json metadata files in `conda-meta` folder within environment).
+    /// Besides the dependencies, returned Metadata keeps the full `CondaRecord` of the package.
     ///
     /// If an invalid path is given, then an error is returned.
     pub fn from_json<'a, P>(path: &P) -> Result<Self, io::Error>
@@ -76,44 +409,265 @@ impl Metadata {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
 
-        let m = serde_json::from_reader(reader)?;
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        let mut m = Metadata::deserialize(&value)?;
+        let record = CondaRecord::deserialize(&value)?;
+        m.pypi_names = distribution_names(record.installed_paths());
+        m.record = Some(record);
         Ok(m)
     }
 
-    // TODO for now it supports only conda environments where all packages were installed by conda
-    //  mixed (pip-conda) environments will be supported soon
+    /// Returns new instance of Metadata of conda package known only from `conda-meta/history`
+    /// (e.g. removed since then and missing in package cache), so it has neither dependencies nor files.
+    pub fn from_history_package(package: &HistoryPackage) -> Self {
+        Self {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            record: Some(CondaRecord {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                build: package.build.clone(),
+                channel: package.channel.clone(),
+                ..CondaRecord::default()
+            }),
+            ..Self::default()
+        }
+    }
+
     /// Returns new instance of Metadata from given name.
-    /// It goes through `CONDA_METADATA` and tries to get package from given name.
+    /// It goes through `CONDA_METADATA` and tries to get package from given name,
+    /// falling back to PEP 503 canonical name (e.g. `PyYAML` finds `pyyaml`).
     ///
     /// If an invalid name is given, then an error is returned.
     pub fn from_name<T: AsRef<str>>(name: T) -> Result<Self, io::Error> {
-        match CONDA_METADATA.get(name.as_ref()) {
+        match find_metadata(&CONDA_METADATA, name.as_ref()) {
             Some(metadata) => {
                 let m = metadata.clone();
-                return Ok(m);
+                Ok(m)
             }
+            None => Err(io::Error::other(format!(
+                "Package '{}' not found",
+                name.as_ref()
+            ))),
+        }
+    }
+}
+
+#[doc(hidden)]
+/// Returns PEP 503 canonical names of Python distributions whose `*.dist-info` or `*.egg-info`
+/// directories are among given paths, e.g. `torch` for `lib/python3.8/site-packages/torch-1.7.0.dist-info/RECORD`.
+fn distribution_names(paths: Vec<&str>) -> Vec<String> {
+    let mut names: Vec<String> = paths
+        .iter()
+        .flat_map(|path| path.split('/'))
+        .filter_map(|component| {
+            component
+                .strip_suffix(".dist-info")
+                .or_else(|| component.strip_suffix(".egg-info"))
+        })
+        .filter_map(|stem| stem.split('-').next())
+        .filter(|name| !name.is_empty())
+        .map(canonicalize_name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[doc(hidden)]
+/// Adds files of given dist-info (or egg-info) directory, that are not listed among installed files yet,
+/// so the metadata directory is owned by the distribution even without complete `RECORD` (or `installed-files.txt`).
+fn add_info_dir_files(path: &Path, record_files: &mut Vec<RecordFile>) -> Result<(), io::Error> {
+    let info_dir = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    for entry in path.read_dir()?.filter_map(|e| e.ok()) {
+        if !entry.path().is_file() {
+            continue;
+        }
+        let file = format!("{}/{}", info_dir, entry.file_name().to_string_lossy());
+        if !record_files.iter().any(|f| f.path == file) {
+            record_files.push(RecordFile {
+                path: file,
+                ..Default::default()
+            })
+        }
+    }
+    Ok(())
+}
+
+#[doc(hidden)]
+/// Reads `RECORD` file of dist-info, which is a CSV file with `path,hash,size` rows.
+fn read_record<R: BufRead>(reader: R) -> Result<Vec<RecordFile>, io::Error> {
+    let mut files = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // paths containing commas are quoted
+        let mut columns: Vec<String> = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    columns.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => columns.push(String::new()),
+                c => columns.last_mut().unwrap().push(c),
+            }
+        }
+        let mut columns = columns.into_iter();
+        let path = columns.next().unwrap_or_default();
+        let hash = columns.next().filter(|h| !h.is_empty());
+        let size = match columns.next().filter(|s| !s.is_empty()) {
+            Some(size) => Some(size.parse::<u64>().map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid size in RECORD line '{}'", line),
+                )
+            })?),
+            None => None,
+        };
+        files.push(RecordFile { path, hash, size })
+    }
+    Ok(files)
+}
+
+#[doc(hidden)]
+/// Reads `requires.txt` file of `.egg-info` distribution.
+/// Requirements listed under `[extra]`, `[:marker]` or `[extra:marker]` sections
+/// get the section converted into their environment marker.
+fn read_requires_txt<R: BufRead>(reader: R) -> Result<Vec<Requirement>, io::Error> {
+    let mut requires: Vec<Requirement> = vec![];
+    let mut section_marker: Option<Marker> = None;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            let section = section.strip_suffix(']').ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid section '{}'", line),
+                )
+            })?;
+            let (extra, marker) = section.split_once(':').unwrap_or((section, ""));
+            let mut markers = vec![];
+            if !marker.trim().is_empty() {
+                markers.push(Marker::parse(marker)?);
+            }
+            if !extra.trim().is_empty() {
+                markers.push(Marker::Expression(
+                    MarkerValue::Variable(String::from("extra")),
+                    MarkerOperator::Equal,
+                    MarkerValue::Literal(extra.trim().to_string()),
+                ));
+            }
+            section_marker = match markers.len() {
+                0 => None,
+                1 => markers.pop(),
+                _ => Some(Marker::And(markers)),
+            };
+            continue;
+        }
+        let mut requirement = match Requirement::parse(line) {
+            Ok(requirement) => requirement,
+            Err(e) => {
+                log::warn!("{} in requires.txt, skipped", e);
+                continue;
+            }
+        };
+        requirement.marker = match (requirement.marker.take(), &section_marker) {
+            (Some(own), Some(section)) => Some(Marker::And(vec![own, section.clone()])),
+            (own, section) => own.or_else(|| section.clone()),
+        };
+        requires.push(requirement)
+    }
+    Ok(requires)
+}
+
+#[doc(hidden)]
+/// Returns names of extras used by markers of given requirements (in order of appearance).
+fn requires_txt_extras(requires: &[Requirement]) -> Vec<String> {
+    fn collect(marker: &Marker, extras: &mut Vec<String>) {
+        match marker {
+            Marker::Expression(MarkerValue::Variable(v), _, MarkerValue::Literal(extra))
+                if v == "extra" && !extras.contains(extra) =>
+            {
+                extras.push(extra.clone())
+            }
+            Marker::And(markers) | Marker::Or(markers) => {
+                markers.iter().for_each(|m| collect(m, extras))
+            }
+            _ => {}
+        }
+    }
+    let mut extras = vec![];
+    for marker in requires.iter().filter_map(|r| r.marker.as_ref()) {
+        collect(marker, &mut extras)
+    }
+    extras
+}
+
+#[doc(hidden)]
+/// Reads email style headers (RFC 822) of METADATA or PKG-INFO file, in the order they appear.
+/// Reading stops at the first empty line, which separates headers from the description.
+/// Folded headers (continuation lines starting with whitespace) are joined with a new line.
+fn read_headers<R: BufRead>(reader: R) -> Result<Vec<(String, String)>, io::Error> {
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            match headers.last_mut() {
+                Some((_, value)) => {
+                    value.push('\n');
+                    value.push_str(line.trim());
+                }
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Unexpected continuation line '{}'", line),
+                    ))
+                }
+            }
+            continue;
+        }
+        match line.split_once(':') {
+            Some((key, value)) => headers.push((key.trim().to_string(), value.trim().to_string())),
             None => {
                 return Err(io::Error::new(
-                    ErrorKind::Other,
-                    format!("Package '{}' not found", name.as_ref()),
+                    ErrorKind::InvalidData,
+                    format!("Invalid header line '{}'", line),
                 ))
             }
         }
     }
+    Ok(headers)
 }
 
 #[doc(hidden)]
-/// Function is used for custom serialization of the `required_dist` Metadata attribute.
+/// Function is used for custom serialization of the `depends` Metadata attribute.
 /// It's able to skip some low level dependencies during deserialization.
 /// It looks for `depends` section in Metadata files.
-fn string_or_seq_string<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+fn string_or_seq_string<'de, D>(deserializer: D) -> Result<Vec<MatchSpec>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    struct StringOrVec(PhantomData<Vec<String>>);
+    struct StringOrVec(PhantomData<Vec<MatchSpec>>);
 
     impl<'de> de::Visitor<'de> for StringOrVec {
-        type Value = Vec<String>;
+        type Value = Vec<MatchSpec>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             formatter.write_str("string or list of strings")
@@ -123,18 +677,12 @@ where
         where
             E: de::Error,
         {
-            let depends = value.to_owned();
-            // skipping
-            // 1. packages that starts with `python`
-            // 2. packages that starts with `lib`
-            // 3. packages that starts with `_` (underscore), they are really low level
-            if depends.starts_with("python")
-                || depends.starts_with("lib")
-                || depends.starts_with("_")
-            {
-                return Ok(vec![]);
-            } else {
-                return Ok(vec![depends]);
+            match parse_depends(value) {
+                // skipping packages filtered out by the policy (by default `python`, `lib*` and `_*`)
+                Some(depends) if !get_filter_policy().is_excluded(&depends.name) => {
+                    Ok(vec![depends])
+                }
+                _ => Ok(vec![]),
             }
         }
 
@@ -142,18 +690,14 @@ where
         where
             S: de::SeqAccess<'de>,
         {
-            let mut new_seq: Vec<String> = vec![];
+            let mut new_seq: Vec<MatchSpec> = vec![];
             while let Some(item) = visitor.next_element::<String>()? {
-                let seq = item.split(" ").collect::<Vec<_>>();
-                let new_item = seq.get(0).unwrap().to_string();
-                // skipping
-                // 1. packages that starts with `python`
-                // 2. packages that starts with `lib`
-                // 3. packages that starts with `_` (underscore), they are really low level
-                if new_item.starts_with("python")
-                    || new_item.starts_with("lib")
-                    || new_item.starts_with("_")
-                {
+                let new_item = match parse_depends(&item) {
+                    Some(new_item) => new_item,
+                    None => continue,
+                };
+                // skipping packages filtered out by the policy (by default `python`, `lib*` and `_*`)
+                if get_filter_policy().is_excluded(&new_item.name) {
                     continue;
                 } else {
                     new_seq.push(new_item)
@@ -166,40 +710,111 @@ where
     deserializer.deserialize_any(StringOrVec(PhantomData))
 }
 
+#[doc(hidden)]
+/// Parses dependency of conda package. A spec that cannot be parsed is kept by its package name only
+/// (a word it starts with), so a single malformed entry doesn't make the whole record unreadable.
+fn parse_depends(spec: &str) -> Option<MatchSpec> {
+    // legacy form of old records, e.g. `pkg1 (0.0.1)`, has never been matched by version
+    if let Some((name, version)) = spec.trim().split_once(" (") {
+        if version.ends_with(')') && !name.contains(char::is_whitespace) {
+            return Some(MatchSpec::name_only(name, spec));
+        }
+    }
+    match MatchSpec::parse(spec) {
+        Ok(depends) => Some(depends),
+        Err(e) => {
+            let name = spec
+                .trim()
+                .split(|c: char| c.is_whitespace() || "=<>!~[(".contains(c))
+                .next()
+                .filter(|name| !name.is_empty())?;
+            log::warn!("{}, only package name '{}' is kept", e, name);
+            Some(MatchSpec::name_only(name, spec))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json;
 
     #[test]
     fn test_from_file_metadata_requires_dist_empty() {
         let path = "./tests/data/site-packages/numpy-1.19.1.dist-info/METADATA";
         let metadata = Metadata::from_metadata_file(path).unwrap();
+        // descriptive fields are covered by `test_from_file_core_metadata`
         assert_eq!(
-            metadata,
+            Metadata {
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                ..metadata
+            },
             Metadata {
                 name: String::from("numpy"),
                 version: String::from("1.19.1"),
-                requires_dist: vec![]
+                depends: vec![],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: None,
+                pypi_names: vec![],
             }
         )
     }
 
+    #[test]
+    fn test_from_file_metadata_requires_dist_invalid() {
+        // given:
+        let path = "./tests/data/site-packages/brokenreq-0.1.0.dist-info/METADATA";
+        // when:
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // then:
+        // malformed requirements are skipped, the distribution is kept
+        assert_eq!(metadata.name, "brokenreq");
+        assert_eq!(
+            metadata.requires_dist,
+            vec![Requirement::parse("six (>=1.15)").unwrap()]
+        )
+    }
+
     #[test]
     fn test_from_file_metadata_requires_dist_non_empty() {
         let path = "./tests/data/site-packages/astroid-2.4.2.dist-info/METADATA";
         let metadata = Metadata::from_metadata_file(path).unwrap();
+        // descriptive fields are covered by `test_from_file_core_metadata`
         assert_eq!(
-            metadata,
+            Metadata {
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                ..metadata
+            },
             Metadata {
                 name: String::from("astroid"),
                 version: String::from("2.4.2"),
+                depends: vec![],
                 requires_dist: vec![
-                    String::from("lazy-object-proxy"),
-                    String::from("six"),
-                    String::from("wrapt"),
-                    String::from("typed-ast"),
-                ]
+                    Requirement::parse("lazy-object-proxy (==1.4.*)").unwrap(),
+                    Requirement::parse("six (~=1.12)").unwrap(),
+                    Requirement::parse("wrapt (~=1.11)").unwrap(),
+                    Requirement::parse(
+                        r#"typed-ast (<1.5,>=1.4.0) ; implementation_name == "cpython" and python_version < "3.8""#
+                    )
+                    .unwrap(),
+                ],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: None,
+                pypi_names: vec![],
             }
         )
     }
@@ -208,30 +823,340 @@ mod tests {
     fn test_from_file_metadata_requires_dist_non_empty_provides_extra_non_empty() {
         let path = "./tests/data/site-packages/mypy-0.782.dist-info/METADATA";
         let metadata = Metadata::from_metadata_file(path).unwrap();
+        // descriptive fields are covered by `test_from_file_core_metadata`
         assert_eq!(
-            metadata,
+            Metadata {
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                ..metadata
+            },
             Metadata {
                 name: String::from("mypy"),
                 version: String::from("0.782"),
+                depends: vec![],
                 requires_dist: vec![
-                    String::from("typed-ast"),
-                    String::from("typing-extensions"),
-                    String::from("mypy-extensions"),
-                ]
+                    Requirement::parse("typed-ast (<1.5.0,>=1.4.0)").unwrap(),
+                    Requirement::parse("typing-extensions (>=3.7.4)").unwrap(),
+                    Requirement::parse("mypy-extensions (<0.5.0,>=0.4.3)").unwrap(),
+                    Requirement::parse("psutil (>=4.0) ; extra == 'dmypy'").unwrap(),
+                ],
+                provides_extra: vec![String::from("dmypy")],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: None,
+                pypi_names: vec![],
             }
         )
     }
 
+    #[test]
+    fn test_from_file_requires_dist_structured() {
+        // given:
+        let path = "./tests/data/site-packages/astroid-2.4.2.dist-info/METADATA";
+        // when:
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // then:
+        let typed_ast = &metadata.requires_dist[3];
+        assert_eq!(typed_ast.name, String::from("typed-ast"));
+        assert_eq!(
+            typed_ast.specifier.to_string(),
+            String::from("<1.5,>=1.4.0")
+        );
+        assert_eq!(
+            typed_ast.marker.as_ref().unwrap().to_string(),
+            String::from(r#"implementation_name == "cpython" and python_version < "3.8""#)
+        );
+    }
+
+    #[test]
+    fn test_extra_requires_dist() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let path = "./tests/data/site-packages/mypy-0.782.dist-info/METADATA";
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // when:
+        let dmypy = metadata.extra_requires_dist("dmypy");
+        let unknown = metadata.extra_requires_dist("unknown");
+        // then:
+        assert_eq!(
+            dmypy,
+            vec![&Requirement::parse("psutil (>=4.0) ; extra == 'dmypy'").unwrap()]
+        );
+        assert!(unknown.is_empty());
+        assert!(!metadata.dependency_names().contains(&"psutil"))
+    }
+
+    #[test]
+    fn test_from_file_core_metadata() {
+        // given:
+        let path = "./tests/data/site-packages/mypy-0.782.dist-info/METADATA";
+        let expected_core_metadata = CoreMetadata {
+            metadata_version: Some(String::from("2.1")),
+            summary: Some(String::from("Optional static typing for Python")),
+            home_page: Some(String::from("http://www.mypy-lang.org/")),
+            license: Some(String::from("MIT License")),
+            classifiers: vec![
+                String::from("Development Status :: 4 - Beta"),
+                String::from("Environment :: Console"),
+                String::from("Intended Audience :: Developers"),
+                String::from("License :: OSI Approved :: MIT License"),
+                String::from("Programming Language :: Python :: 3"),
+                String::from("Programming Language :: Python :: 3.5"),
+                String::from("Programming Language :: Python :: 3.6"),
+                String::from("Programming Language :: Python :: 3.7"),
+                String::from("Programming Language :: Python :: 3.8"),
+                String::from("Topic :: Software Development"),
+            ],
+            requires_python: Some(String::from(">=3.5")),
+        };
+        // when:
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // then:
+        assert_eq!(metadata.core_metadata, Some(expected_core_metadata))
+    }
+
+    #[test]
+    fn test_from_file_folded_description() {
+        // given:
+        let path = "./tests/data/site-packages/certifi-2020.6.20-py3.8.egg-info/PKG-INFO";
+        // when:
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // then:
+        let core_metadata = metadata.core_metadata.unwrap();
+        assert_eq!(core_metadata.license, Some(String::from("MPL-2.0")));
+        assert_eq!(core_metadata.classifiers.len(), 12);
+        assert_eq!(core_metadata.requires_python, None)
+    }
+
+    #[test]
+    fn test_read_headers() {
+        // given:
+        let input = "Metadata-Version: 2.1\nName: pkg\nName-Foo: bar\nLicense: MIT\n        Copyright (c) 2020\nClassifier: A\nClassifier: B\n\nVersion: 9.9.9\n";
+        let expected_headers = vec![
+            (String::from("Metadata-Version"), String::from("2.1")),
+            (String::from("Name"), String::from("pkg")),
+            (String::from("Name-Foo"), String::from("bar")),
+            (
+                String::from("License"),
+                String::from("MIT\nCopyright (c) 2020"),
+            ),
+            (String::from("Classifier"), String::from("A")),
+            (String::from("Classifier"), String::from("B")),
+        ];
+        // when:
+        let headers = read_headers(input.as_bytes()).unwrap();
+        // then:
+        assert_eq!(headers, expected_headers)
+    }
+
+    #[test]
+    fn test_read_headers_invalid() {
+        assert!(read_headers("  folded\nName: pkg\n".as_bytes()).is_err());
+        assert!(read_headers("Name pkg\n".as_bytes()).is_err())
+    }
+
+    #[test]
+    fn test_from_egg_info_requires_txt() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let path = "./tests/data/site-packages/pytest-6.0.1-py3.8.egg-info";
+        // when:
+        let metadata = Metadata::from_egg_info(path).unwrap();
+        // then:
+        assert_eq!(metadata.name, String::from("pytest"));
+        assert_eq!(metadata.requires_dist.len(), 18);
+        assert_eq!(
+            metadata.dependency_names(),
+            vec![
+                "attrs",
+                "iniconfig",
+                "more-itertools",
+                "packaging",
+                "pluggy",
+                "py",
+                "toml"
+            ]
+        );
+        assert_eq!(
+            metadata.provides_extra,
+            vec![String::from("checkqa_mypy"), String::from("testing")]
+        );
+        assert_eq!(
+            metadata.requires_dist[7],
+            Requirement::parse(r#"pathlib2>=2.2.0; python_version < "3.6""#).unwrap()
+        );
+        assert_eq!(
+            metadata.extra_requires_dist("checkqa_mypy"),
+            vec![&Requirement::parse(r#"mypy==0.780; extra == "checkqa_mypy""#).unwrap()]
+        );
+        assert_eq!(metadata.extra_requires_dist("testing").len(), 6)
+    }
+
+    #[test]
+    fn test_from_egg_info_file() {
+        // given:
+        let path = "./tests/data/site-packages/pkg1-1.0.0-just-PKG-INFO.egg-info/PKG-INFO";
+        // when:
+        let metadata = Metadata::from_egg_info(path).unwrap();
+        // then:
+        assert_eq!(
+            Metadata {
+                record_files: vec![],
+                location: None,
+                ..metadata.clone()
+            },
+            Metadata::from_metadata_file(path).unwrap()
+        );
+        // the file itself is the only file of the distribution
+        assert_eq!(metadata.record_files.len(), 1);
+        assert_eq!(metadata.record_files[0].path, "PKG-INFO")
+    }
+
+    #[test]
+    fn test_from_egg_info_installed_files() {
+        // given:
+        let path =
+            "./tests/data/mixed-env/lib/python3.8/site-packages/legacy_pkg-1.0-py3.8.egg-info";
+        // when:
+        let metadata = Metadata::from_egg_info(path).unwrap();
+        let mut files: Vec<&str> = metadata
+            .record_files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        files.sort();
+        // then:
+        assert_eq!(
+            files,
+            vec![
+                "legacy_pkg-1.0-py3.8.egg-info/../legacy_pkg/__init__.py",
+                "legacy_pkg-1.0-py3.8.egg-info/PKG-INFO",
+                "legacy_pkg-1.0-py3.8.egg-info/installed-files.txt",
+                "legacy_pkg-1.0-py3.8.egg-info/requires.txt",
+            ]
+        );
+        assert_eq!(
+            metadata.location,
+            Some(PathBuf::from(
+                "./tests/data/mixed-env/lib/python3.8/site-packages"
+            ))
+        )
+    }
+
+    #[test]
+    fn test_read_requires_txt_extra_with_marker() {
+        // given:
+        let input = "six\n\n[socks:sys_platform == \"win32\"]\nwin-inet-pton\n";
+        let expected_requires = vec![
+            Requirement::parse("six").unwrap(),
+            Requirement::parse(r#"win-inet-pton; sys_platform == "win32" and extra == "socks""#)
+                .unwrap(),
+        ];
+        // when:
+        let requires = read_requires_txt(input.as_bytes()).unwrap();
+        // then:
+        assert_eq!(requires, expected_requires)
+    }
+
+    #[test]
+    fn test_read_requires_txt_invalid_requirement() {
+        // given:
+        let input = "six\n>=1.0\n[socks]\nPySocks (>=1.5.6\n";
+        // when:
+        let requires = read_requires_txt(input.as_bytes()).unwrap();
+        // then:
+        assert_eq!(requires, vec![Requirement::parse("six").unwrap()])
+    }
+
+    #[test]
+    fn test_from_dist_info() {
+        // given:
+        let path = "./tests/data/site-packages/mylib-0.1.0.dist-info";
+        let expected_direct_url = DirectUrl {
+            url: String::from("https://github.com/example/mylib.git"),
+            vcs_info: Some(VcsInfo {
+                vcs: String::from("git"),
+                commit_id: String::from("7921be1537eac1e97bc40179a57f0349c2aee67d"),
+                requested_revision: Some(String::from("main")),
+            }),
+            dir_info: Some(DirInfo { editable: true }),
+            archive_info: None,
+        };
+        // when:
+        let metadata = Metadata::from_dist_info(path).unwrap();
+        // then:
+        assert_eq!(metadata.name, String::from("mylib"));
+        assert_eq!(metadata.installed_by, Some(String::from("uv")));
+        assert_eq!(metadata.installer(), Installer::Uv);
+        assert_eq!(metadata.direct_url, Some(expected_direct_url))
+    }
+
+    #[test]
+    fn test_from_dist_info_without_installer() {
+        let path = "./tests/data/site-packages/numpy-1.19.1.dist-info";
+        let metadata = Metadata::from_dist_info(path).unwrap();
+        assert_eq!(metadata.installer(), Installer::Pip);
+        assert_eq!(metadata.direct_url, None)
+    }
+
+    #[test]
+    fn test_read_record() {
+        // given:
+        let input = "six.py,sha256=U4Z_yv534W5CNyjY9i8V1OXY2SjAny8y2L5vDLhhThI,34159\n\"dir,with,commas/a.py\",sha256=abc,1\nsix-1.15.0.dist-info/RECORD,,\n";
+        let expected_files = vec![
+            RecordFile {
+                path: String::from("six.py"),
+                hash: Some(String::from(
+                    "sha256=U4Z_yv534W5CNyjY9i8V1OXY2SjAny8y2L5vDLhhThI",
+                )),
+                size: Some(34159),
+            },
+            RecordFile {
+                path: String::from("dir,with,commas/a.py"),
+                hash: Some(String::from("sha256=abc")),
+                size: Some(1),
+            },
+            RecordFile {
+                path: String::from("six-1.15.0.dist-info/RECORD"),
+                hash: None,
+                size: None,
+            },
+        ];
+        // when:
+        let files = read_record(input.as_bytes()).unwrap();
+        // then:
+        assert_eq!(files, expected_files)
+    }
+
     #[test]
     fn test_from_file_pkginfo_requires_dist_empty() {
         let path = "./tests/data/site-packages/certifi-2020.6.20-py3.8.egg-info/PKG-INFO";
         let metadata = Metadata::from_metadata_file(path).unwrap();
+        // descriptive fields are covered by `test_from_file_core_metadata`
         assert_eq!(
-            metadata,
+            Metadata {
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                ..metadata
+            },
             Metadata {
                 name: String::from("certifi"),
                 version: String::from("2020.6.20"),
-                requires_dist: vec![]
+                depends: vec![],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: None,
+                pypi_names: vec![],
             }
         )
     }
@@ -245,7 +1170,22 @@ mod tests {
             Metadata {
                 name: String::from("pkg1"),
                 version: String::from("1.0.0"),
-                requires_dist: vec![String::from("pkg2")]
+                depends: vec![],
+                requires_dist: vec![
+                    Requirement::parse("pkg2 (==0.0.1)").unwrap(),
+                    Requirement::parse(r#"pk3-test ; extra == "test""#).unwrap(),
+                ],
+                provides_extra: vec![String::from("test")],
+                core_metadata: Some(CoreMetadata {
+                    metadata_version: Some(String::from("2.1")),
+                    ..CoreMetadata::default()
+                }),
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: None,
+                pypi_names: vec![],
             }
         )
     }
@@ -257,7 +1197,39 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
+            depends: vec![],
             requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: Some(CondaRecord {
+                name: String::from("pkg1"),
+                version: String::from("0.0.1"),
+                build: String::from("py38_0"),
+                build_number: 0,
+                channel: Some(String::from("conda-forge")),
+                subdir: Some(String::from("linux-64")),
+                url: Some(String::from(
+                    "https://conda.anaconda.org/conda-forge/linux-64/pkg1-0.0.1-py38_0.tar.bz2",
+                )),
+                filename: Some(String::from("pkg1-0.0.1-py38_0.tar.bz2")),
+                md5: Some(String::from("d41d8cd98f00b204e9800998ecf8427e")),
+                sha256: Some(String::from(
+                    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                )),
+                size: Some(1024),
+                timestamp: Some(1596000000000),
+                license: Some(String::from("MIT")),
+                requested_spec: Some(String::from("pkg1")),
+                depends: vec![],
+                constrains: vec![],
+                files: vec![],
+                paths_data: None,
+            }),
+            pypi_names: vec![],
         };
         // when:
         let m = Metadata::from_json(path).unwrap();
@@ -272,7 +1244,22 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg2a"),
             version: String::from("0.0.1"),
-            requires_dist: vec![String::from("pkg1")],
+            depends: vec![MatchSpec::name_only("pkg1", "pkg1 (0.0.1)")],
+            requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: Some(CondaRecord {
+                name: String::from("pkg2a"),
+                version: String::from("0.0.1"),
+                channel: Some(String::from("conda-forge")),
+                depends: vec![String::from("pkg1 (0.0.1)")],
+                ..Default::default()
+            }),
+            pypi_names: vec![],
         };
         // when:
         let m = Metadata::from_json(path).unwrap();
@@ -287,7 +1274,25 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg3"),
             version: String::from("0.0.1"),
-            requires_dist: vec![String::from("pkg2a"), String::from("pkg2b")],
+            depends: vec![
+                MatchSpec::name_only("pkg2a", "pkg2a (0.0.1)"),
+                MatchSpec::name_only("pkg2b", "pkg2b (0.0.1)"),
+            ],
+            requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: Some(CondaRecord {
+                name: String::from("pkg3"),
+                version: String::from("0.0.1"),
+                channel: Some(String::from("conda-forge")),
+                depends: vec![String::from("pkg2a (0.0.1)"), String::from("pkg2b (0.0.1)")],
+                ..Default::default()
+            }),
+            pypi_names: vec![],
         };
         // when:
         let m = Metadata::from_json(path).unwrap();
@@ -295,17 +1300,33 @@ mod tests {
         assert_eq!(m, expected_m)
     }
 
+    #[test]
+    fn test_from_json_constrained_dependencies() {
+        // given:
+        let path = "./tests/data/conda-meta/pkg3b-0.0.1.json";
+        let expected_depends = vec![
+            MatchSpec::parse("pkg2a >=0.0.1,<1.0a0").unwrap(),
+            MatchSpec::parse("pkg2b 0.0.1.*").unwrap(),
+        ];
+        // when:
+        let m = Metadata::from_json(path).unwrap();
+        // then:
+        assert_eq!(m.depends, expected_depends);
+        assert_eq!(m.depends[0].version, Some(String::from(">=0.0.1,<1.0a0")));
+        assert_eq!(m.depends[1].version, Some(String::from("0.0.1.*")))
+    }
+
     #[test]
     fn test_from_name() {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
         let expected_name = String::from("pkg3");
-        let expected_requires_dist = vec![String::from("pkg2a"), String::from("pkg2b")];
+        let expected_dependency_names = vec!["pkg2a", "pkg2b"];
         // when:
         let m = Metadata::from_name(String::from("pkg3")).unwrap();
         // then:
         assert_eq!(m.name, expected_name);
-        assert_eq!(m.requires_dist, expected_requires_dist)
+        assert_eq!(m.dependency_names(), expected_dependency_names)
     }
 
     #[test]
@@ -315,7 +1336,34 @@ mod tests {
         // when:
         let m = Metadata::from_name(String::from("unknown"));
         // then:
-        assert_eq!(m.is_err(), true)
+        assert!(m.is_err())
+    }
+
+    #[test]
+    fn test_from_name_normalized_name() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        // when:
+        let m = Metadata::from_name(String::from("PKG3"));
+        // then:
+        assert_eq!(m.unwrap().name, "pkg3")
+    }
+
+    #[test]
+    fn test_distribution_names() {
+        // given:
+        let paths = vec![
+            "lib/python3.8/site-packages/torch-1.7.0.dist-info/RECORD",
+            "lib/python3.8/site-packages/torch-1.7.0.dist-info/METADATA",
+            "lib/python3.8/site-packages/torch/__init__.py",
+            "lib/python3.8/site-packages/ruamel.yaml-0.16.12-py3.8.egg-info/PKG-INFO",
+            "Lib/site-packages/Opencv_Python-4.5.1.dist-info/RECORD",
+            "bin/torch-info",
+        ];
+        // when:
+        let names = distribution_names(paths);
+        // then:
+        assert_eq!(names, vec!["opencv-python", "ruamel-yaml", "torch"])
     }
 
     #[test]
@@ -329,7 +1377,16 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
-            requires_dist: vec![String::from("pkg2")],
+            depends: vec![MatchSpec::parse("pkg2").unwrap()],
+            requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
@@ -348,7 +1405,16 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
+            depends: vec![],
             requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
@@ -356,6 +1422,26 @@ mod tests {
         assert_eq!(m, expected_m)
     }
 
+    #[test]
+    fn test_deserialize_metadata_not_skip_python_prefixed() {
+        // given:
+        let string = r#"{
+            "name": "pkg1",
+            "version": "0.0.1",
+            "depends": ["python >=3.8", "python-dateutil", "pythran"]
+        }"#;
+        // when:
+        let m: Metadata = serde_json::from_str(string).unwrap();
+        // then:
+        assert_eq!(
+            m.depends,
+            vec![
+                MatchSpec::parse("python-dateutil").unwrap(),
+                MatchSpec::parse("pythran").unwrap()
+            ]
+        )
+    }
+
     #[test]
     fn test_deserialize_metadata_skip_lib() {
         // given:
@@ -367,7 +1453,16 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
+            depends: vec![],
             requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
@@ -386,7 +1481,16 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
+            depends: vec![],
             requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
@@ -405,7 +1509,19 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
-            requires_dist: vec![String::from("pkg2a"), String::from("pkg2b")],
+            depends: vec![
+                MatchSpec::parse("pkg2a").unwrap(),
+                MatchSpec::parse("pkg2b").unwrap(),
+            ],
+            requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
@@ -424,11 +1540,67 @@ mod tests {
         let expected_m = Metadata {
             name: String::from("pkg1"),
             version: String::from("0.0.1"),
-            requires_dist: vec![String::from("pkg2a"), String::from("pkg2b")],
+            depends: vec![
+                MatchSpec::parse("pkg2a").unwrap(),
+                MatchSpec::parse("pkg2b").unwrap(),
+            ],
+            requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         // when:
         let m: Metadata = serde_json::from_str(string).unwrap();
         // then:
         assert_eq!(m, expected_m)
     }
+
+    #[test]
+    fn test_deserialize_metadata_depends_keep_constraints() {
+        // given:
+        let string = r#"{
+            "name": "pkg1",
+            "version": "0.0.1",
+            "depends": ["numpy >=1.19,<2.0a0", "mkl 2020.2 256", "python >=3.8,<3.9.0a0"]
+        }"#;
+        let expected_constraints = vec![
+            Some(String::from(">=1.19,<2.0a0")),
+            Some(String::from("2020.2 256")),
+        ];
+        // when:
+        let m: Metadata = serde_json::from_str(string).unwrap();
+        // then:
+        assert_eq!(m.dependency_names(), vec!["numpy", "mkl"]);
+        assert_eq!(
+            m.depends.iter().map(|d| d.constraint()).collect::<Vec<_>>(),
+            expected_constraints
+        )
+    }
+
+    #[test]
+    fn test_deserialize_metadata_invalid_depends() {
+        // given:
+        let string = r#"{
+            "name": "pkg1",
+            "version": "0.0.1",
+            "depends": ["numpy >=", "pkg2 (0.0.1)", "six >=1.15", ">=1.0"]
+        }"#;
+        // when:
+        let m: Metadata = serde_json::from_str(string).unwrap();
+        // then:
+        // invalid specs are kept by name only, specs without name are skipped
+        assert_eq!(
+            m.depends,
+            vec![
+                MatchSpec::name_only("numpy", "numpy >="),
+                MatchSpec::name_only("pkg2", "pkg2 (0.0.1)"),
+                MatchSpec::parse("six >=1.15").unwrap(),
+            ]
+        )
+    }
 }
@@ -1,18 +1,44 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::fmt;
 
-use crate::metadata::Metadata;
+use crate::matchspec::MatchSpec;
+use crate::metadata::{CondaRecord, DirectUrl, Metadata};
+use crate::utils::{canonicalize_name, find_conda_dependency, find_metadata, CONDA_METADATA};
 
 /// Enum that represents the installer of given Python package.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub(crate) enum Installer {
     Pip,
+    Uv,
+    Poetry,
+    /// Any other tool, named in `INSTALLER` file of the distribution.
+    Other(String),
+    #[default]
     Conda,
 }
 
-impl Default for Installer {
-    fn default() -> Self {
-        Installer::Conda
+/// Returns Installer from the content of `INSTALLER` file of Python distribution.
+impl From<&str> for Installer {
+    fn from(installer: &str) -> Self {
+        match installer.trim().to_lowercase().as_str() {
+            "" | "pip" => Installer::Pip,
+            "uv" => Installer::Uv,
+            "poetry" => Installer::Poetry,
+            other => Installer::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Installer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Installer::Pip => write!(f, "pip"),
+            Installer::Uv => write!(f, "uv"),
+            Installer::Poetry => write!(f, "poetry"),
+            Installer::Other(installer) => write!(f, "{}", installer),
+            Installer::Conda => write!(f, "conda"),
+        }
     }
 }
 
@@ -22,89 +48,301 @@ pub struct Package {
     version: String,
     requires: Vec<Package>,
     pub(crate) installer: Installer,
+    record: Option<CondaRecord>,
+    /// Origin of the package (PEP 610), if it was installed from URL, VCS or local directory.
+    direct_url: Option<DirectUrl>,
+    /// Version (and build) constraint of the dependency edge pointing to the package.
+    constraint: Option<String>,
+    /// Set if the package is already among its ancestors, so its dependencies are not repeated.
+    cycle: bool,
+}
+
+impl Package {
+    /// Returns full conda record of the package, if package was installed by conda.
+    pub fn record(&self) -> Option<&CondaRecord> {
+        self.record.as_ref()
+    }
 }
 
 impl fmt::Display for Package {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         let package_string = if self.version == "any" {
-            format!("{}", self.name)
+            self.name.to_string()
         } else {
             format!("{} (v{})", self.name, self.version)
         };
         write!(f, "{}", package_string)?;
+        if let Some(constraint) = &self.constraint {
+            write!(f, " [{}]", constraint)?;
+        }
+        if self.cycle {
+            write!(f, " (cycle)")?;
+        }
         Ok(())
     }
 }
 
 impl From<Metadata> for Package {
     fn from(metadata: Metadata) -> Self {
-        // get list of packages from Metadata.requires_dist
-        let requires: Vec<Package> = metadata
-            .requires_dist
-            .iter()
-            .map(|name| Metadata::from_name(name).unwrap())
-            .map(|m| Package::from(m))
-            .collect();
-        // TODO add required by
-        Package {
-            name: metadata.name,
-            version: metadata.version,
-            requires: requires,
-            installer: Installer::default(),
-        }
+        package_tree(&metadata, &CONDA_METADATA, &mut HashSet::new())
     }
 }
 
+#[doc(hidden)]
+/// Returns dependency tree of given package, resolving dependencies among given metadata.
+/// `path` keeps canonical names of packages from the root down to the package, so dependency cycles
+/// (common among Python distributions, e.g. `sphinx` and `sphinxcontrib-*`) end with a node marked as a cycle.
+fn package_tree(
+    metadata: &Metadata,
+    all_metadata: &HashMap<String, Metadata>,
+    path: &mut HashSet<String>,
+) -> Package {
+    let mut package = Package {
+        installer: metadata.installer(),
+        name: metadata.name.clone(),
+        version: metadata.version.clone(),
+        requires: vec![],
+        record: metadata.record.clone(),
+        direct_url: metadata.direct_url.clone(),
+        constraint: None,
+        cycle: false,
+    };
+    let canonical = canonicalize_name(&metadata.name);
+    if path.contains(&canonical) {
+        package.cycle = true;
+        return package;
+    }
+    path.insert(canonical.clone());
+    // get list of packages from Metadata.depends, keeping constraints of every edge
+    // (dependencies that are not installed are skipped, `check` command reports them)
+    let conda_requires = metadata.depends.iter().filter_map(|dependency| {
+        let dependency_metadata = find_metadata(all_metadata, &dependency.name)?;
+        Some((dependency_metadata, dependency.constraint()))
+    });
+    // and from Metadata.requires_dist
+    let pip_requires = metadata
+        .applicable_requires_dist()
+        .filter_map(|requirement| {
+            let requirement_metadata = find_metadata(all_metadata, &requirement.name)?;
+            let constraint = match requirement.specifier.is_empty() {
+                true => None,
+                false => Some(requirement.specifier.to_string()),
+            };
+            Some((requirement_metadata, constraint))
+        });
+    let requires: Vec<(&Metadata, Option<String>)> = conda_requires.chain(pip_requires).collect();
+    // TODO add required by
+    package.requires = requires
+        .into_iter()
+        .map(|(m, constraint)| {
+            let mut child = package_tree(m, all_metadata, path);
+            child.constraint = constraint;
+            child
+        })
+        .collect();
+    path.remove(&canonical);
+    package
+}
+
 /// Converts Package into String.
 ///
 /// It takes into account Installer type, in order to create proper String representation of the Package.
-impl Into<String> for Package {
-    fn into(self) -> String {
-        match &self.installer {
-            Installer::Pip => return format!("{}=={}", self.name, self.version),
-            Installer::Conda => return format!("{}={}", self.name, self.version),
+/// Python packages installed from VCS, URL or local directory are represented by their source,
+/// e.g. `-e git+https://github.com/org/repo.git@<sha>#egg=name`, instead of version pin.
+impl From<Package> for String {
+    fn from(package: Package) -> Self {
+        match (&package.installer, &package.direct_url) {
+            (Installer::Conda, _) => format!("{}={}", package.name, package.version),
+            (_, Some(direct_url)) => {
+                let source = match &direct_url.vcs_info {
+                    Some(vcs_info) => {
+                        format!("{}+{}@{}", vcs_info.vcs, direct_url.url, vcs_info.commit_id)
+                    }
+                    None => direct_url.url.clone(),
+                };
+                match (direct_url.is_editable(), &direct_url.vcs_info) {
+                    (true, Some(_)) => format!("-e {}#egg={}", source, package.name),
+                    (true, None) => format!("-e {}", source),
+                    (false, _) => format!("{} @ {}", package.name, source),
+                }
+            }
+            (_, None) => format!("{}=={}", package.name, package.version),
         }
     }
 }
 
-/// Pretty prints given package.
+/// Pretty prints given package, followed by constraints it puts on other packages.
 pub(crate) fn print_package(package: &Package) {
-    let tree = package_to_lines(package).join("\n");
-    println!("{}", tree)
+    let mut lines = package_to_lines(package);
+    lines.extend(package_constrains_to_lines(package));
+    println!("{}", lines.join("\n"))
+}
+
+/// Pretty prints conda record details of given package.
+pub(crate) fn print_package_info(package: &Package) {
+    let info = package_info_to_lines(package).join("\n");
+    println!("{}", info)
+}
+
+/// Returns `key: value` lines that describe conda record of the Package.
+/// Fields that are not available in the record are skipped.
+pub(crate) fn package_info_to_lines(package: &Package) -> Vec<String> {
+    let mut lines = vec![format!("{}", package)];
+    let record = match package.record() {
+        Some(record) => record,
+        None => {
+            if package.installer != Installer::Conda {
+                lines.push(format!("  installer: {}", package.installer));
+            }
+            if let Some(direct_url) = &package.direct_url {
+                lines.push(format!("  source: {}", direct_url.url));
+                if let Some(vcs_info) = &direct_url.vcs_info {
+                    lines.push(format!("  commit: {}", vcs_info.commit_id));
+                }
+                lines.push(format!("  editable: {}", direct_url.is_editable()));
+            }
+            return lines;
+        }
+    };
+
+    let fields: Vec<(&str, Option<String>)> = vec![
+        (
+            "build",
+            Some(record.build.clone()).filter(|b| !b.is_empty()),
+        ),
+        ("build number", Some(record.build_number.to_string())),
+        ("channel", record.channel.clone()),
+        ("subdir", record.subdir.clone()),
+        ("url", record.url.clone()),
+        ("filename", record.filename.clone()),
+        ("md5", record.md5.clone()),
+        ("sha256", record.sha256.clone()),
+        ("size", record.size.map(|s| s.to_string())),
+        ("timestamp", record.timestamp.map(|t| t.to_string())),
+        ("license", record.license.clone()),
+        ("requested spec", record.requested_spec.clone()),
+    ];
+    lines.extend(
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|v| format!("  {}: {}", key, v))),
+    );
+    lines
 }
 
 /// Returns a pretty formated String representation of the Package.
 pub(crate) fn package_to_lines(package: &Package) -> Vec<String> {
     let mut lines = vec![format!("{}", package)];
-    let children = &package.requires[..];
-    if let Some((last_child, non_last_children)) = children.split_last() {
-        let child_node_lines = non_last_children.iter().flat_map(|child| {
-            package_to_lines(child)
-                .iter()
-                .enumerate()
-                .map(|(idx, child_line)| {
-                    if idx == 0 {
-                        format!("├── {}", child_line)
-                    } else {
-                        format!("│   {}", child_line)
+    let children: Vec<Vec<String>> = package.requires.iter().map(package_to_lines).collect();
+    lines.extend(children_to_lines(children));
+    lines
+}
+
+/// Returns constraints (`constrains` of conda record) the package puts on other packages,
+/// together with the information whether installed packages satisfy them.
+/// If the package doesn't define any constraint, an empty list is returned.
+pub(crate) fn package_constrains_to_lines(package: &Package) -> Vec<String> {
+    constrains_to_lines(package, &CONDA_METADATA)
+}
+
+#[doc(hidden)]
+fn constrains_to_lines(package: &Package, metadata: &HashMap<String, Metadata>) -> Vec<String> {
+    let constrains: Vec<MatchSpec> = package
+        .record()
+        .iter()
+        .flat_map(|r| r.constrains.iter())
+        .filter_map(|c| MatchSpec::parse(c).ok())
+        .collect();
+    if constrains.is_empty() {
+        return vec![];
+    }
+    let children: Vec<Vec<String>> = constrains
+        .iter()
+        .map(|spec| {
+            let constraint = spec.constraint().unwrap_or_else(|| String::from("*"));
+            let installed = find_conda_dependency(metadata, &spec.name);
+            let line = match installed {
+                Some(installed) => {
+                    let violated = match &installed.record {
+                        Some(record) => !spec.matches(record),
+                        None => false,
+                    };
+                    let status = if violated { " (violated)" } else { "" };
+                    format!(
+                        "{} (v{}) [{}]{}",
+                        installed.name, installed.version, constraint, status
+                    )
+                }
+                None => format!("{} [{}] (not installed)", spec.name, constraint),
+            };
+            vec![line]
+        })
+        .collect();
+    let mut lines = vec![String::from("constrains:")];
+    lines.extend(children_to_lines(children));
+    lines
+}
+
+/// Pretty prints requirements pulled in by every extra declared by given package.
+pub(crate) fn print_package_extras(metadata: &Metadata) {
+    let extras = package_extras_to_lines(metadata).join("\n");
+    println!("{}", extras)
+}
+
+/// Returns requirements of every extra declared by the package (e.g. `[complete]` of `dask`),
+/// together with the information whether they are installed in the environment.
+pub(crate) fn package_extras_to_lines(metadata: &Metadata) -> Vec<String> {
+    let mut lines = vec![format!("{} (v{})", metadata.name, metadata.version)];
+    if metadata.provides_extra.is_empty() {
+        lines.push(String::from("  no extras declared"));
+    }
+    for extra in metadata.provides_extra.iter() {
+        lines.push(format!("[{}]", extra));
+        let children: Vec<Vec<String>> = metadata
+            .extra_requires_dist(extra)
+            .iter()
+            .map(|requirement| {
+                let constraint = Some(requirement.specifier.to_string()).filter(|s| !s.is_empty());
+                match Metadata::from_name(&requirement.name) {
+                    Ok(installed) => {
+                        let mut package = Package::from(installed);
+                        package.constraint = constraint;
+                        package_to_lines(&package)
                     }
-                })
-                .collect::<Vec<String>>()
-        });
-        let last_child_node_lines = package_to_lines(last_child);
-        let formatted_last_child_node_lines_iter =
-            last_child_node_lines
-                .iter()
-                .enumerate()
-                .map(|(idx, child_line)| {
-                    if idx == 0 {
-                        format!("└── {}", child_line)
-                    } else {
-                        format!("    {}", child_line)
+                    Err(_) => {
+                        let constraint =
+                            constraint.map(|c| format!(" [{}]", c)).unwrap_or_default();
+                        vec![format!(
+                            "{}{} (not installed)",
+                            requirement.name, constraint
+                        )]
                     }
-                });
-        let children_lines = child_node_lines.chain(formatted_last_child_node_lines_iter);
-        lines.extend(children_lines);
+                }
+            })
+            .collect();
+        lines.extend(children_to_lines(children));
+    }
+    lines
+}
+
+#[doc(hidden)]
+/// Joins lines of the child nodes into a tree branches.
+fn children_to_lines(children: Vec<Vec<String>>) -> Vec<String> {
+    let mut lines = vec![];
+    let children_count = children.len();
+    for (child_idx, child_lines) in children.into_iter().enumerate() {
+        let last = child_idx + 1 == children_count;
+        lines.extend(
+            child_lines
+                .into_iter()
+                .enumerate()
+                .map(|(idx, child_line)| match (idx == 0, last) {
+                    (true, false) => format!("├── {}", child_line),
+                    (false, false) => format!("│   {}", child_line),
+                    (true, true) => format!("└── {}", child_line),
+                    (false, true) => format!("    {}", child_line),
+                }),
+        );
     }
     lines
 }
@@ -112,6 +350,9 @@ pub(crate) fn package_to_lines(package: &Package) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::metadata::{ArchiveInfo, DirInfo, VcsInfo};
+    use crate::pep508::Requirement;
+    use crate::utils::{get_conda_meta_metadata, get_prefix_metadata};
 
     #[test]
     fn test_package_display_with_version() {
@@ -120,11 +361,31 @@ mod tests {
             version: String::from("1.0.0"),
             requires: vec![],
             installer: Installer::default(),
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
         };
         let package_str = format!("{}", package);
         assert_eq!(package_str, "package (v1.0.0)".to_string())
     }
 
+    #[test]
+    fn test_package_display_with_constraint() {
+        let package = Package {
+            name: String::from("package"),
+            version: String::from("1.0.0"),
+            requires: vec![],
+            installer: Installer::default(),
+            record: None,
+            direct_url: None,
+            constraint: Some(String::from(">=1.0,<2.0a0")),
+            cycle: false,
+        };
+        let package_str = format!("{}", package);
+        assert_eq!(package_str, "package (v1.0.0) [>=1.0,<2.0a0]".to_string())
+    }
+
     #[test]
     fn test_package_display_any_version() {
         let package = Package {
@@ -132,6 +393,10 @@ mod tests {
             version: String::from("any"),
             requires: vec![],
             installer: Installer::default(),
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
         };
         let package_str = format!("{}", package);
         assert_eq!(package_str, "package".to_string())
@@ -142,13 +407,26 @@ mod tests {
         let metadata = Metadata {
             name: String::from("some_package"),
             version: String::from("1.0.0"),
+            depends: vec![],
             requires_dist: vec![],
+            provides_extra: vec![],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
         };
         let expected_package = Package {
             name: String::from("some_package"),
             version: String::from("1.0.0"),
             requires: vec![],
-            installer: Installer::default(),
+            installer: Installer::Pip,
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
         };
         assert_eq!(Package::from(metadata), expected_package)
     }
@@ -160,6 +438,10 @@ mod tests {
             version: String::from("0.0.1"),
             requires: vec![],
             installer: Installer::Conda,
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
         }
         .into();
         assert_eq!(p, String::from("conda1=0.0.1"))
@@ -172,8 +454,274 @@ mod tests {
             version: String::from("0.0.1"),
             requires: vec![],
             installer: Installer::Pip,
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
         }
         .into();
         assert_eq!(p, String::from("pip1==0.0.1"))
     }
+
+    #[test]
+    fn test_package_info_to_lines() {
+        // given:
+        let package = Package {
+            name: String::from("package"),
+            version: String::from("1.0.0"),
+            requires: vec![],
+            installer: Installer::Conda,
+            record: Some(CondaRecord {
+                name: String::from("package"),
+                version: String::from("1.0.0"),
+                build: String::from("py38_0"),
+                channel: Some(String::from("conda-forge")),
+                size: Some(1024),
+                ..Default::default()
+            }),
+            direct_url: None,
+            constraint: None,
+            cycle: false,
+        };
+        let expected_lines = vec![
+            String::from("package (v1.0.0)"),
+            String::from("  build: py38_0"),
+            String::from("  build number: 0"),
+            String::from("  channel: conda-forge"),
+            String::from("  size: 1024"),
+        ];
+        // when:
+        let lines = package_info_to_lines(&package);
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_package_info_to_lines_no_record() {
+        // given:
+        let package = Package {
+            name: String::from("package"),
+            version: String::from("1.0.0"),
+            requires: vec![],
+            installer: Installer::Pip,
+            record: None,
+            direct_url: None,
+            constraint: None,
+            cycle: false,
+        };
+        // when:
+        let lines = package_info_to_lines(&package);
+        // then:
+        assert_eq!(
+            lines,
+            vec![
+                String::from("package (v1.0.0)"),
+                String::from("  installer: pip")
+            ]
+        )
+    }
+
+    #[test]
+    fn test_convert_from_metadata_keeps_constraints() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = Metadata::from_name("pkg3b").unwrap();
+        let expected_lines = vec![
+            String::from("pkg3b (v0.0.1)"),
+            String::from("├── pkg2a (v0.0.1) [>=0.0.1,<1.0a0]"),
+            String::from("│   └── pkg1 (v0.0.1)"),
+            String::from("└── pkg2b (v0.0.1) [0.0.1.*]"),
+        ];
+        // when:
+        let package = Package::from(metadata);
+        // then:
+        assert_eq!(package_to_lines(&package), expected_lines)
+    }
+
+    #[test]
+    fn test_package_tree_cycle() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/cycle-env");
+        let expected_lines = vec![
+            "Sphinx (v3.2.1)",
+            "└── sphinxcontrib-applehelp (v1.0.2)",
+            "    └── Sphinx (v3.2.1) [>=2.0] (cycle)",
+        ];
+        // when:
+        let package = package_tree(&metadata["Sphinx"], &metadata, &mut HashSet::new());
+        // then:
+        assert_eq!(package_to_lines(&package), expected_lines)
+    }
+
+    #[test]
+    fn test_package_extras_to_lines() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = Metadata {
+            name: String::from("pkg4"),
+            version: String::from("1.0.0"),
+            depends: vec![],
+            requires_dist: vec![
+                Requirement::parse("pkg2b").unwrap(),
+                Requirement::parse(r#"pkg2a (>=0.0.1) ; extra == "complete""#).unwrap(),
+                Requirement::parse(r#"pkg5 (<2) ; extra == "complete""#).unwrap(),
+            ],
+            provides_extra: vec![String::from("complete"), String::from("empty")],
+            core_metadata: None,
+            installed_by: None,
+            direct_url: None,
+            record_files: vec![],
+            location: None,
+            record: None,
+            pypi_names: vec![],
+        };
+        let expected_lines = vec![
+            "pkg4 (v1.0.0)",
+            "[complete]",
+            "├── pkg2a (v0.0.1) [>=0.0.1]",
+            "│   └── pkg1 (v0.0.1)",
+            "└── pkg5 [<2] (not installed)",
+            "[empty]",
+        ];
+        // when:
+        let lines = package_extras_to_lines(&metadata);
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_package_extras_to_lines_no_extras() {
+        // given:
+        let path = "./tests/data/site-packages/numpy-1.19.1.dist-info/METADATA";
+        let metadata = Metadata::from_metadata_file(path).unwrap();
+        // when:
+        let lines = package_extras_to_lines(&metadata);
+        // then:
+        assert_eq!(lines, vec!["numpy (v1.19.1)", "  no extras declared"])
+    }
+
+    #[test]
+    fn test_installer_from_str() {
+        assert_eq!(Installer::from("pip\n"), Installer::Pip);
+        assert_eq!(Installer::from(""), Installer::Pip);
+        assert_eq!(Installer::from("uv"), Installer::Uv);
+        assert_eq!(Installer::from("Poetry"), Installer::Poetry);
+        assert_eq!(
+            Installer::from("pdm"),
+            Installer::Other(String::from("pdm"))
+        )
+    }
+
+    #[test]
+    fn test_into_string_direct_url() {
+        // given:
+        let package = |direct_url: DirectUrl| Package {
+            name: String::from("mylib"),
+            version: String::from("0.1.0"),
+            requires: vec![],
+            installer: Installer::Pip,
+            record: None,
+            direct_url: Some(direct_url),
+            constraint: None,
+            cycle: false,
+        };
+        let vcs_info = Some(VcsInfo {
+            vcs: String::from("git"),
+            commit_id: String::from("7921be1"),
+            requested_revision: None,
+        });
+        let editable = Some(DirInfo { editable: true });
+        let cases = vec![
+            (
+                DirectUrl {
+                    url: String::from("https://github.com/example/mylib.git"),
+                    vcs_info: vcs_info.clone(),
+                    dir_info: editable.clone(),
+                    ..DirectUrl::default()
+                },
+                "-e git+https://github.com/example/mylib.git@7921be1#egg=mylib",
+            ),
+            (
+                DirectUrl {
+                    url: String::from("https://github.com/example/mylib.git"),
+                    vcs_info,
+                    ..DirectUrl::default()
+                },
+                "mylib @ git+https://github.com/example/mylib.git@7921be1",
+            ),
+            (
+                DirectUrl {
+                    url: String::from("file:///home/user/mylib"),
+                    dir_info: editable,
+                    ..DirectUrl::default()
+                },
+                "-e file:///home/user/mylib",
+            ),
+            (
+                DirectUrl {
+                    url: String::from("https://example.com/mylib-0.1.0.tar.gz"),
+                    archive_info: Some(ArchiveInfo { hash: None }),
+                    ..DirectUrl::default()
+                },
+                "mylib @ https://example.com/mylib-0.1.0.tar.gz",
+            ),
+        ];
+        for (direct_url, expected) in cases {
+            // when:
+            let package_string: String = package(direct_url).into();
+            // then:
+            assert_eq!(package_string, expected)
+        }
+    }
+
+    #[test]
+    fn test_package_info_to_lines_direct_url() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let path = "./tests/data/site-packages/mylib-0.1.0.dist-info";
+        let mut metadata = Metadata::from_dist_info(path).unwrap();
+        // `six` is not installed in test environment
+        metadata.requires_dist.clear();
+        let expected_lines = vec![
+            "mylib (v0.1.0)",
+            "  installer: uv",
+            "  source: https://github.com/example/mylib.git",
+            "  commit: 7921be1537eac1e97bc40179a57f0349c2aee67d",
+            "  editable: true",
+        ];
+        // when:
+        let lines = package_info_to_lines(&Package::from(metadata));
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_package_constrains_to_lines() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_conda_meta_metadata("./tests/data/constrained-env");
+        let package = Package::from(metadata["pkg-c"].clone());
+        let expected_lines = vec![
+            "constrains:",
+            "├── pkg1 (v0.0.1) [>=1.0] (violated)",
+            "├── pkg1 (v0.0.1) [0.0.*]",
+            "└── pkg404 [2.*] (not installed)",
+        ];
+        // when:
+        let lines = constrains_to_lines(&package, &metadata);
+        // then:
+        assert_eq!(lines, expected_lines)
+    }
+
+    #[test]
+    fn test_package_constrains_to_lines_no_constrains() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let package = Package::from(Metadata::from_name("pkg3").unwrap());
+        // when:
+        let lines = package_constrains_to_lines(&package);
+        // then:
+        assert!(lines.is_empty())
+    }
 }
@@ -0,0 +1,698 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    #[doc(hidden)]
+    /// Regex taken from PEP 440 appendix, it accepts all non-normalized forms of the version.
+    static ref PEP440_VERSION_REGEX: Regex = Regex::new(
+        r"(?xi)
+        ^\s*v?
+        (?:(?P<epoch>[0-9]+)!)?
+        (?P<release>[0-9]+(?:\.[0-9]+)*)
+        (?P<pre>
+            [-_.]?
+            (?P<pre_l>alpha|a|beta|b|preview|pre|c|rc)
+            [-_.]?
+            (?P<pre_n>[0-9]+)?
+        )?
+        (?P<post>
+            (?:-(?P<post_n1>[0-9]+))
+            |
+            (?:
+                [-_.]?
+                (?P<post_l>post|rev|r)
+                [-_.]?
+                (?P<post_n2>[0-9]+)?
+            )
+        )?
+        (?P<dev>
+            [-_.]?
+            (?P<dev_l>dev)
+            [-_.]?
+            (?P<dev_n>[0-9]+)?
+        )?
+        (?:\+(?P<local>[a-z0-9]+(?:[-_.][a-z0-9]+)*))?
+        \s*$"
+    )
+    .unwrap();
+}
+
+/// Segment of the local version, numeric segments sort after alphanumeric ones.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LocalSegment {
+    Str(String),
+    Int(u64),
+}
+
+impl fmt::Display for LocalSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            LocalSegment::Str(s) => write!(f, "{}", s),
+            LocalSegment::Int(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+/// Helper used to build comparison key of the version, where missing values have to sort
+/// either before or after all given values.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Bound<T> {
+    NegInf,
+    Value(T),
+    PosInf,
+}
+
+/// Python package version, compliant with PEP 440.
+///
+/// Versions are normalized during parsing, so e.g. `1.0-ALPHA.1` and `1.0a1` are equal
+/// and both are displayed as `1.0a1`.
+#[derive(Clone, Debug)]
+pub struct Pep440Version {
+    epoch: u64,
+    release: Vec<u64>,
+    pre: Option<(String, u64)>,
+    post: Option<u64>,
+    dev: Option<u64>,
+    local: Vec<LocalSegment>,
+}
+
+impl Pep440Version {
+    /// Returns new instance of Pep440Version from given version string.
+    ///
+    /// If an invalid version is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(version: T) -> Result<Self, io::Error> {
+        let original = version.as_ref();
+        let captures = PEP440_VERSION_REGEX
+            .captures(original)
+            .ok_or_else(|| invalid_version(original, "doesn't comply with PEP 440"))?;
+        let number = |name: &str| -> Result<Option<u64>, io::Error> {
+            captures
+                .name(name)
+                .map(|m| {
+                    m.as_str()
+                        .parse::<u64>()
+                        .map_err(|_| invalid_version(original, "number is too large"))
+                })
+                .transpose()
+        };
+
+        let epoch = number("epoch")?.unwrap_or(0);
+        let release = captures["release"]
+            .split('.')
+            .map(|r| {
+                r.parse::<u64>()
+                    .map_err(|_| invalid_version(original, "number is too large"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let pre = match captures.name("pre_l") {
+            Some(label) => {
+                let label = match label.as_str().to_lowercase().as_str() {
+                    "alpha" | "a" => "a",
+                    "beta" | "b" => "b",
+                    _ => "rc",
+                };
+                Some((label.to_string(), number("pre_n")?.unwrap_or(0)))
+            }
+            None => None,
+        };
+        let post = if captures.name("post").is_some() {
+            Some(number("post_n1")?.or(number("post_n2")?).unwrap_or(0))
+        } else {
+            None
+        };
+        let dev = if captures.name("dev").is_some() {
+            Some(number("dev_n")?.unwrap_or(0))
+        } else {
+            None
+        };
+        let local = match captures.name("local") {
+            Some(local) => local
+                .as_str()
+                .to_lowercase()
+                .split(['-', '_', '.'])
+                .map(|s| match s.parse::<u64>() {
+                    Ok(i) => LocalSegment::Int(i),
+                    Err(_) => LocalSegment::Str(s.to_string()),
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Ok(Self {
+            epoch,
+            release,
+            pre,
+            post,
+            dev,
+            local,
+        })
+    }
+
+    /// Returns `true` if it's a pre-release or development release.
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    /// Returns `true` if it's a post-release.
+    pub fn is_postrelease(&self) -> bool {
+        self.post.is_some()
+    }
+
+    /// Returns version without local part, e.g. `1.0+ubuntu1` -> `1.0`.
+    pub fn public(&self) -> Self {
+        Self {
+            local: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// Returns version with epoch and release segment only, e.g. `1!1.0rc1.post2` -> `1!1.0`.
+    pub fn base(&self) -> Self {
+        Self {
+            epoch: self.epoch,
+            release: self.release.clone(),
+            pre: None,
+            post: None,
+            dev: None,
+            local: vec![],
+        }
+    }
+
+    #[doc(hidden)]
+    #[allow(clippy::type_complexity)]
+    fn key(
+        &self,
+    ) -> (
+        u64,
+        Vec<u64>,
+        Bound<(String, u64)>,
+        Bound<u64>,
+        Bound<u64>,
+        Bound<Vec<LocalSegment>>,
+    ) {
+        // trailing zeros don't matter, 1.0 == 1.0.0
+        let mut release = self.release.clone();
+        while release.len() > 1 && release.last() == Some(&0) {
+            release.pop();
+        }
+        // dev release without pre and post release sorts before all pre-releases
+        let pre = match (&self.pre, self.post, self.dev) {
+            (None, None, Some(_)) => Bound::NegInf,
+            (None, _, _) => Bound::PosInf,
+            (Some(pre), _, _) => Bound::Value(pre.clone()),
+        };
+        let post = self.post.map_or(Bound::NegInf, Bound::Value);
+        let dev = self.dev.map_or(Bound::PosInf, Bound::Value);
+        let local = if self.local.is_empty() {
+            Bound::NegInf
+        } else {
+            Bound::Value(self.local.clone())
+        };
+        (self.epoch, release, pre, post, dev, local)
+    }
+
+    #[doc(hidden)]
+    /// Splits version into tokens used by prefix matching, e.g. `1!1.2rc1` -> [`1`, `1`, `2`, `rc1`].
+    fn tokens(&self, release_len: usize) -> Vec<String> {
+        let mut tokens = vec![self.epoch.to_string()];
+        tokens.extend((0..release_len).map(|idx| self.release.get(idx).unwrap_or(&0).to_string()));
+        if let Some((label, n)) = &self.pre {
+            tokens.push(format!("{}{}", label, n));
+        }
+        if let Some(post) = self.post {
+            tokens.push(format!("post{}", post));
+        }
+        if let Some(dev) = self.dev {
+            tokens.push(format!("dev{}", dev));
+        }
+        tokens
+    }
+}
+
+impl Ord for Pep440Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl PartialOrd for Pep440Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Pep440Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pep440Version {}
+
+impl FromStr for Pep440Version {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pep440Version::parse(s)
+    }
+}
+
+/// Formats version in its normalized form.
+impl fmt::Display for Pep440Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?;
+        }
+        let release: Vec<String> = self.release.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", release.join("."))?;
+        if let Some((label, n)) = &self.pre {
+            write!(f, "{}{}", label, n)?;
+        }
+        if let Some(post) = self.post {
+            write!(f, ".post{}", post)?;
+        }
+        if let Some(dev) = self.dev {
+            write!(f, ".dev{}", dev)?;
+        }
+        if !self.local.is_empty() {
+            let local: Vec<String> = self.local.iter().map(|l| l.to_string()).collect();
+            write!(f, "+{}", local.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+fn invalid_version(version: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid version '{}': {}", version, reason),
+    )
+}
+
+/// Version comparison operators defined by PEP 440.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Arbitrary,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let operator = match self {
+            Operator::Compatible => "~=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::LessEqual => "<=",
+            Operator::GreaterEqual => ">=",
+            Operator::Less => "<",
+            Operator::Greater => ">",
+            Operator::Arbitrary => "===",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+/// Single version specifier, e.g. `>=1.0`, `==1.4.*` or `~=2.2`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Specifier {
+    pub operator: Operator,
+    /// Version as written in the specifier (without `.*` suffix).
+    pub version: String,
+    /// `true` if specifier ends with `.*`, allowed only with `==` and `!=` operators.
+    pub wildcard: bool,
+}
+
+impl Specifier {
+    /// Returns new instance of Specifier from given string.
+    ///
+    /// If an invalid specifier is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(specifier: T) -> Result<Self, io::Error> {
+        let original = specifier.as_ref();
+        let specifier = original.trim();
+        let operators = [
+            ("===", Operator::Arbitrary),
+            ("~=", Operator::Compatible),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessEqual),
+            (">=", Operator::GreaterEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+        let (operator, version) = operators
+            .iter()
+            .find_map(|(op_str, op)| specifier.strip_prefix(op_str).map(|v| (op.clone(), v)))
+            .ok_or_else(|| invalid_specifier(original, "missing operator"))?;
+        let version = version.trim();
+        if version.is_empty() {
+            return Err(invalid_specifier(original, "missing version"));
+        }
+        if operator == Operator::Arbitrary {
+            return Ok(Self {
+                operator,
+                version: version.to_string(),
+                wildcard: false,
+            });
+        }
+
+        let (version, wildcard) = match version.strip_suffix(".*") {
+            Some(v) if operator == Operator::Equal || operator == Operator::NotEqual => (v, true),
+            Some(_) => return Err(invalid_specifier(original, "'.*' is not allowed here")),
+            None => (version, false),
+        };
+        let parsed = Pep440Version::parse(version)?;
+        let equality = operator == Operator::Equal || operator == Operator::NotEqual;
+        if !parsed.local.is_empty() && !equality {
+            return Err(invalid_specifier(
+                original,
+                "local version is not allowed here",
+            ));
+        }
+        if wildcard && !parsed.local.is_empty() {
+            return Err(invalid_specifier(
+                original,
+                "local version is not allowed with '.*'",
+            ));
+        }
+        if operator == Operator::Compatible && parsed.release.len() < 2 {
+            return Err(invalid_specifier(
+                original,
+                "'~=' requires at least two release segments",
+            ));
+        }
+        Ok(Self {
+            operator,
+            version: version.to_string(),
+            wildcard,
+        })
+    }
+
+    /// Returns `true` if given version satisfies the specifier.
+    /// Pre-releases are always taken into account, the same way `pip check` does it.
+    pub fn contains(&self, version: &Pep440Version) -> bool {
+        if self.operator == Operator::Arbitrary {
+            return version.to_string().eq_ignore_ascii_case(&self.version);
+        }
+        // version has been validated during parsing
+        let spec = match Pep440Version::parse(&self.version) {
+            Ok(spec) => spec,
+            Err(_) => return false,
+        };
+        match self.operator {
+            Operator::Compatible => {
+                let prefix = Pep440Version {
+                    release: spec.release[..spec.release.len() - 1].to_vec(),
+                    ..spec.base()
+                };
+                version.public() >= spec && prefix_matches(version, &prefix)
+            }
+            Operator::Equal | Operator::NotEqual => {
+                let equal = if self.wildcard {
+                    prefix_matches(version, &spec)
+                } else if spec.local.is_empty() {
+                    version.public() == spec
+                } else {
+                    *version == spec
+                };
+                equal == (self.operator == Operator::Equal)
+            }
+            Operator::LessEqual => version.public() <= spec,
+            Operator::GreaterEqual => version.public() >= spec,
+            Operator::Less => {
+                // `<1.0` doesn't match pre-releases of 1.0, unless spec is a pre-release itself
+                *version < spec
+                    && !(!spec.is_prerelease()
+                        && version.is_prerelease()
+                        && version.base() == spec.base())
+            }
+            Operator::Greater => {
+                // `>1.0` doesn't match post-releases and local versions of 1.0
+                *version > spec
+                    && !(!spec.is_postrelease()
+                        && version.is_postrelease()
+                        && version.base() == spec.base())
+                    && (version.local.is_empty() || version.base() != spec.base())
+            }
+            Operator::Arbitrary => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Specifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}{}", self.operator, self.version)?;
+        if self.wildcard {
+            write!(f, ".*")?;
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+/// Returns `true` if version starts with given prefix, e.g. `1.4.5` matches `1.4.*`.
+/// Release segments are padded with zeros, so `1.4` matches `1.4.0.*`.
+fn prefix_matches(version: &Pep440Version, prefix: &Pep440Version) -> bool {
+    let release_len = version.release.len().max(prefix.release.len());
+    let prefix_tokens = prefix.tokens(prefix.release.len());
+    let version_tokens = version.tokens(release_len);
+    version_tokens.len() >= prefix_tokens.len()
+        && version_tokens[..prefix_tokens.len()] == prefix_tokens[..]
+}
+
+#[doc(hidden)]
+fn invalid_specifier(specifier: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid specifier '{}': {}", specifier, reason),
+    )
+}
+
+/// Comma separated list of version specifiers, e.g. `>=1.0,!=1.3.4.*,<2.0`.
+/// Version has to satisfy all of them. Empty set matches every version.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpecifierSet {
+    pub specifiers: Vec<Specifier>,
+}
+
+impl SpecifierSet {
+    /// Returns new instance of SpecifierSet from given string.
+    ///
+    /// If any of the specifiers is invalid, then an error is returned.
+    pub fn parse<T: AsRef<str>>(specifiers: T) -> Result<Self, io::Error> {
+        let specifiers = specifiers
+            .as_ref()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Specifier::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { specifiers })
+    }
+
+    /// Returns `true` if given version satisfies all of the specifiers.
+    pub fn contains(&self, version: &Pep440Version) -> bool {
+        self.specifiers.iter().all(|s| s.contains(version))
+    }
+
+    /// Returns `true` if there are no specifiers in the set.
+    pub fn is_empty(&self) -> bool {
+        self.specifiers.is_empty()
+    }
+}
+
+impl FromStr for SpecifierSet {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SpecifierSet::parse(s)
+    }
+}
+
+impl fmt::Display for SpecifierSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let specifiers: Vec<String> = self.specifiers.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", specifiers.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> Pep440Version {
+        Pep440Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_version_normalization() {
+        let cases = vec![
+            ("1.0", "1.0"),
+            ("v1.0", "1.0"),
+            (" 1.0 ", "1.0"),
+            ("1.0-ALPHA.1", "1.0a1"),
+            ("1.0alpha", "1.0a0"),
+            ("1.0.BETA2", "1.0b2"),
+            ("1.0c1", "1.0rc1"),
+            ("1.0pre1", "1.0rc1"),
+            ("1.0preview1", "1.0rc1"),
+            ("1.0-1", "1.0.post1"),
+            ("1.0.r2", "1.0.post2"),
+            ("1.0rev", "1.0.post0"),
+            ("1.0_post_3", "1.0.post3"),
+            ("1.0-dev", "1.0.dev0"),
+            ("1.0.DEV-5", "1.0.dev5"),
+            ("1!2.0", "1!2.0"),
+            ("1.0+Ubuntu-1", "1.0+ubuntu.1"),
+            ("1.0a1.post2.dev3+abc.5", "1.0a1.post2.dev3+abc.5"),
+        ];
+        for (version, expected) in cases {
+            assert_eq!(v(version).to_string(), expected, "normalized '{}'", version)
+        }
+    }
+
+    #[test]
+    fn test_version_invalid() {
+        let invalid_versions = vec!["", "1.0.x", "1.0+", "1.0+abc..1", "a1.0", "1.0~1", "1..0"];
+        for version in invalid_versions {
+            assert!(
+                Pep440Version::parse(version).is_err(),
+                "'{}' should be invalid",
+                version
+            )
+        }
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        // ordering taken from PEP 440 examples
+        let versions = vec![
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc.5",
+            "1.0+abc.7",
+            "1.0+5",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.1.dev1",
+            "1!0.1",
+        ];
+        for pair in versions.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_version_equal_trailing_zeros() {
+        assert_eq!(v("1.0"), v("1.0.0"));
+        assert_eq!(v("1.0a1"), v("1.0.0alpha1"));
+        assert_ne!(v("1.0"), v("1.0+abc"));
+    }
+
+    #[test]
+    fn test_specifier_contains() {
+        let cases = vec![
+            ("==1.0", "1.0", true),
+            ("==1.0", "1.0.0", true),
+            ("==1.0", "1.0+local", true),
+            ("==1.0+local", "1.0", false),
+            ("==1.0+local", "1.0+local", true),
+            ("==1.4.*", "1.4.5", true),
+            ("==1.4.*", "1.4", true),
+            ("==1.4.*", "1.40", false),
+            ("==1.4.0.*", "1.4", true),
+            ("==1.4.*", "1.4.5rc1", true),
+            ("!=1.4.*", "1.4.5", false),
+            ("!=1.4.*", "1.5", true),
+            ("!=1.0", "1.0.1", true),
+            ("~=2.2", "2.3", true),
+            ("~=2.2", "3.0", false),
+            ("~=2.2", "2.1", false),
+            ("~=1.4.5", "1.4.9", true),
+            ("~=1.4.5", "1.5.0", false),
+            ("~=1.4.5a4", "1.4.5", true),
+            ("<=2.0", "2.0", true),
+            ("<=2.0", "2.0+local", true),
+            (">=2.0", "2.0.post1", true),
+            ("<2.0", "1.9", true),
+            ("<2.0", "2.0rc1", false),
+            ("<2.0rc2", "2.0rc1", true),
+            (">2.0", "2.1", true),
+            (">2.0", "2.0.post1", false),
+            (">2.0.post1", "2.0.post2", true),
+            (">2.0", "2.0+local", false),
+            ("===1.0-foo", "1.0", false),
+            ("===1.0", "1.0", true),
+        ];
+        for (specifier, version, expected) in cases {
+            assert_eq!(
+                Specifier::parse(specifier).unwrap().contains(&v(version)),
+                expected,
+                "'{}' contains '{}'",
+                specifier,
+                version
+            )
+        }
+    }
+
+    #[test]
+    fn test_specifier_invalid() {
+        let invalid_specifiers = vec![
+            "1.0",
+            "==",
+            ">=1.0.*",
+            "~=1.*",
+            "~=1",
+            "<1.0+local",
+            "==1.0+local.*",
+            "==1.x",
+        ];
+        for specifier in invalid_specifiers {
+            assert!(
+                Specifier::parse(specifier).is_err(),
+                "'{}' should be invalid",
+                specifier
+            )
+        }
+    }
+
+    #[test]
+    fn test_specifier_set() {
+        // given:
+        let specifiers = SpecifierSet::parse(">=1.0, !=1.3.4.*, <2.0").unwrap();
+        // then:
+        assert_eq!(specifiers.to_string(), ">=1.0,!=1.3.4.*,<2.0");
+        assert!(specifiers.contains(&v("1.5")));
+        assert!(!specifiers.contains(&v("1.3.4.1")));
+        assert!(!specifiers.contains(&v("2.0")));
+        assert!(!specifiers.contains(&v("0.9")));
+    }
+
+    #[test]
+    fn test_specifier_set_empty() {
+        let specifiers = SpecifierSet::parse("").unwrap();
+        assert!(specifiers.is_empty());
+        assert!(specifiers.contains(&v("1.0")))
+    }
+}
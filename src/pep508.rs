@@ -0,0 +1,861 @@
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::metadata::CondaRecord;
+use crate::pep440::{Pep440Version, Specifier, SpecifierSet};
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref NAME_REGEX: Regex =
+        Regex::new(r"^[A-Za-z0-9](?:[A-Za-z0-9._-]*[A-Za-z0-9])?").unwrap();
+}
+
+/// Environment marker variables defined by PEP 508 (and their legacy, dotted spellings).
+const MARKER_VARIABLES: [&str; 18] = [
+    "python_version",
+    "python_full_version",
+    "os_name",
+    "sys_platform",
+    "platform_release",
+    "platform_system",
+    "platform_version",
+    "platform_machine",
+    "platform_python_implementation",
+    "implementation_name",
+    "implementation_version",
+    "extra",
+    "os.name",
+    "sys.platform",
+    "platform.version",
+    "platform.machine",
+    "platform.python_implementation",
+    "python_implementation",
+];
+
+/// Python package requirement, compliant with PEP 508,
+/// e.g. `requests[security] (>=2.8.1,==2.8.*) ; python_version < "2.7"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    pub specifier: SpecifierSet,
+    pub url: Option<String>,
+    pub marker: Option<Marker>,
+}
+
+impl Requirement {
+    /// Returns new instance of Requirement from given string.
+    ///
+    /// Legacy form with version only in parentheses (e.g. `pkg (1.0)`) is treated as `pkg (==1.0)`.
+    ///
+    /// If an invalid requirement is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(requirement: T) -> Result<Self, io::Error> {
+        let original = requirement.as_ref();
+        let requirement = original.trim();
+
+        // name
+        let name = NAME_REGEX
+            .find(requirement)
+            .ok_or_else(|| invalid_requirement(original, "missing package name"))?
+            .as_str()
+            .to_string();
+        let mut rest = requirement[name.len()..].trim_start();
+
+        // extras
+        let mut extras = vec![];
+        if let Some(stripped) = rest.strip_prefix('[') {
+            let end = stripped
+                .find(']')
+                .ok_or_else(|| invalid_requirement(original, "unclosed extras"))?;
+            for extra in stripped[..end].split(',').map(str::trim) {
+                if extra.is_empty() {
+                    continue;
+                }
+                if NAME_REGEX.find(extra).map(|m| m.as_str()) != Some(extra) {
+                    return Err(invalid_requirement(original, "invalid extra name"));
+                }
+                extras.push(extra.to_string());
+            }
+            rest = stripped[end + 1..].trim_start();
+        }
+
+        // url or version specifier, followed by optional marker
+        let mut url = None;
+        let specifier;
+        let marker_str;
+        if let Some(stripped) = rest.strip_prefix('@') {
+            let stripped = stripped.trim_start();
+            // url can contain `;`, so marker has to be separated with whitespace
+            let end = stripped.find(char::is_whitespace).unwrap_or(stripped.len());
+            if end == 0 {
+                return Err(invalid_requirement(original, "missing url"));
+            }
+            url = Some(stripped[..end].to_string());
+            specifier = SpecifierSet::default();
+            marker_str = stripped[end..].trim_start();
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            let mut version = rest[..end].trim();
+            if let Some(stripped) = version.strip_prefix('(') {
+                version = stripped
+                    .strip_suffix(')')
+                    .ok_or_else(|| invalid_requirement(original, "unclosed parentheses"))?
+                    .trim();
+                if version.starts_with(|c: char| c.is_ascii_digit()) {
+                    specifier = SpecifierSet::parse(format!("=={}", version))?;
+                } else {
+                    specifier = SpecifierSet::parse(version)?;
+                }
+            } else {
+                specifier = SpecifierSet::parse(version)?;
+            }
+            marker_str = &rest[end..];
+        }
+
+        let marker = if marker_str.is_empty() {
+            None
+        } else {
+            let marker_str = marker_str
+                .strip_prefix(';')
+                .ok_or_else(|| invalid_requirement(original, "unexpected characters"))?;
+            Some(Marker::parse(marker_str)?)
+        };
+
+        Ok(Self {
+            name,
+            extras,
+            specifier,
+            url,
+            marker,
+        })
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Requirement::parse(s)
+    }
+}
+
+/// Formats Requirement in its canonical form, e.g. `requests[security]>=2.8.1; python_version < "2.7"`.
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name)?;
+        if !self.extras.is_empty() {
+            write!(f, "[{}]", self.extras.join(","))?;
+        }
+        write!(f, "{}", self.specifier)?;
+        if let Some(url) = &self.url {
+            write!(f, " @ {}", url)?;
+            if self.marker.is_some() {
+                write!(f, " ")?;
+            }
+        }
+        if let Some(marker) = &self.marker {
+            write!(f, "; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+fn invalid_requirement(requirement: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid requirement '{}': {}", requirement, reason),
+    )
+}
+
+/// Operators allowed in environment markers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkerOperator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Arbitrary,
+    In,
+    NotIn,
+}
+
+impl fmt::Display for MarkerOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let operator = match self {
+            MarkerOperator::Compatible => "~=",
+            MarkerOperator::Equal => "==",
+            MarkerOperator::NotEqual => "!=",
+            MarkerOperator::LessEqual => "<=",
+            MarkerOperator::GreaterEqual => ">=",
+            MarkerOperator::Less => "<",
+            MarkerOperator::Greater => ">",
+            MarkerOperator::Arbitrary => "===",
+            MarkerOperator::In => "in",
+            MarkerOperator::NotIn => "not in",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+/// Either side of the marker expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+impl fmt::Display for MarkerValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            MarkerValue::Variable(variable) => write!(f, "{}", variable),
+            MarkerValue::Literal(literal) => write!(f, "\"{}\"", literal),
+        }
+    }
+}
+
+/// Environment marker, e.g. `implementation_name == "cpython" and python_version < "3.8"`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Marker {
+    Expression(MarkerValue, MarkerOperator, MarkerValue),
+    And(Vec<Marker>),
+    Or(Vec<Marker>),
+}
+
+impl Marker {
+    /// Returns new instance of Marker from given string.
+    ///
+    /// If an invalid marker is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(marker: T) -> Result<Self, io::Error> {
+        let original = marker.as_ref();
+        let tokens = tokenize_marker(original)?;
+        let mut parser = MarkerParser {
+            original,
+            tokens,
+            pos: 0,
+        };
+        let marker = parser.parse_or()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(invalid_marker(original, "unexpected tokens"));
+        }
+        Ok(marker)
+    }
+
+    /// Returns `true` if marker holds in given environment.
+    pub fn evaluate(&self, environment: &MarkerEnvironment) -> bool {
+        match self {
+            Marker::Expression(left, operator, right) => {
+                let extra = MarkerValue::Variable(String::from("extra"));
+                if left == &extra || right == &extra {
+                    // names of extras are compared after normalization (PEP 685)
+                    let left = normalize_extra(environment.resolve(left));
+                    let right = normalize_extra(environment.resolve(right));
+                    return compare_marker_values(&left, operator, &right);
+                }
+                let left = environment.resolve(left);
+                let right = environment.resolve(right);
+                compare_marker_values(&left, operator, &right)
+            }
+            Marker::And(markers) => markers.iter().all(|m| m.evaluate(environment)),
+            Marker::Or(markers) => markers.iter().any(|m| m.evaluate(environment)),
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Marker::parse(s)
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Marker::Expression(left, operator, right) => {
+                write!(f, "{} {} {}", left, operator, right)
+            }
+            Marker::And(markers) => {
+                let markers: Vec<String> = markers
+                    .iter()
+                    .map(|m| match m {
+                        Marker::Or(_) => format!("({})", m),
+                        _ => m.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", markers.join(" and "))
+            }
+            Marker::Or(markers) => {
+                let markers: Vec<String> = markers.iter().map(|m| m.to_string()).collect();
+                write!(f, "{}", markers.join(" or "))
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+fn invalid_marker(marker: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid marker '{}': {}", marker.trim(), reason),
+    )
+}
+
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq)]
+enum MarkerToken {
+    Variable(String),
+    Literal(String),
+    Operator(MarkerOperator),
+    And,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+#[doc(hidden)]
+/// Splits marker into tokens.
+fn tokenize_marker(marker: &str) -> Result<Vec<MarkerToken>, io::Error> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = marker.chars().collect();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '(' {
+            tokens.push(MarkerToken::LeftParen);
+            pos += 1;
+        } else if c == ')' {
+            tokens.push(MarkerToken::RightParen);
+            pos += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[pos + 1..]
+                .iter()
+                .position(|&q| q == c)
+                .ok_or_else(|| invalid_marker(marker, "unclosed string"))?;
+            tokens.push(MarkerToken::Literal(
+                chars[pos + 1..pos + 1 + end].iter().collect(),
+            ));
+            pos += end + 2;
+        } else if "<>=!~".contains(c) {
+            let op: String = chars[pos..]
+                .iter()
+                .take_while(|c| "<>=!~".contains(**c))
+                .collect();
+            let operator = match op.as_str() {
+                "~=" => MarkerOperator::Compatible,
+                "==" => MarkerOperator::Equal,
+                "!=" => MarkerOperator::NotEqual,
+                "<=" => MarkerOperator::LessEqual,
+                ">=" => MarkerOperator::GreaterEqual,
+                "<" => MarkerOperator::Less,
+                ">" => MarkerOperator::Greater,
+                "===" => MarkerOperator::Arbitrary,
+                _ => return Err(invalid_marker(marker, "invalid operator")),
+            };
+            tokens.push(MarkerToken::Operator(operator));
+            pos += op.chars().count();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let word: String = chars[pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '.')
+                .collect();
+            pos += word.chars().count();
+            let token = match word.as_str() {
+                "and" => MarkerToken::And,
+                "or" => MarkerToken::Or,
+                "in" => MarkerToken::Operator(MarkerOperator::In),
+                "not" => {
+                    // `not` is allowed only as a part of `not in` operator
+                    let next: String = chars[pos..]
+                        .iter()
+                        .skip_while(|c| c.is_whitespace())
+                        .take_while(|c| c.is_ascii_alphabetic())
+                        .collect();
+                    if next != "in" {
+                        return Err(invalid_marker(marker, "expected 'in' after 'not'"));
+                    }
+                    pos += chars[pos..]
+                        .iter()
+                        .take_while(|c| c.is_whitespace())
+                        .count()
+                        + 2;
+                    MarkerToken::Operator(MarkerOperator::NotIn)
+                }
+                variable if MARKER_VARIABLES.contains(&variable) => {
+                    MarkerToken::Variable(variable.to_string())
+                }
+                _ => return Err(invalid_marker(marker, "unknown variable")),
+            };
+            tokens.push(token);
+        } else {
+            return Err(invalid_marker(marker, "unexpected character"));
+        }
+    }
+    Ok(tokens)
+}
+
+#[doc(hidden)]
+/// Simple recursive descent parser of the marker tokens.
+struct MarkerParser<'a> {
+    original: &'a str,
+    tokens: Vec<MarkerToken>,
+    pos: usize,
+}
+
+impl<'a> MarkerParser<'a> {
+    fn next(&mut self) -> Option<MarkerToken> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Marker, io::Error> {
+        let mut markers = vec![self.parse_and()?];
+        while self.tokens.get(self.pos) == Some(&MarkerToken::Or) {
+            self.pos += 1;
+            markers.push(self.parse_and()?);
+        }
+        Ok(if markers.len() == 1 {
+            markers.remove(0)
+        } else {
+            Marker::Or(markers)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Marker, io::Error> {
+        let mut markers = vec![self.parse_atom()?];
+        while self.tokens.get(self.pos) == Some(&MarkerToken::And) {
+            self.pos += 1;
+            markers.push(self.parse_atom()?);
+        }
+        Ok(if markers.len() == 1 {
+            markers.remove(0)
+        } else {
+            Marker::And(markers)
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<Marker, io::Error> {
+        if self.tokens.get(self.pos) == Some(&MarkerToken::LeftParen) {
+            self.pos += 1;
+            let marker = self.parse_or()?;
+            if self.next() != Some(MarkerToken::RightParen) {
+                return Err(invalid_marker(self.original, "unbalanced parentheses"));
+            }
+            return Ok(marker);
+        }
+        let left = self.parse_value()?;
+        let operator = match self.next() {
+            Some(MarkerToken::Operator(operator)) => operator,
+            _ => return Err(invalid_marker(self.original, "expected operator")),
+        };
+        let right = self.parse_value()?;
+        Ok(Marker::Expression(left, operator, right))
+    }
+
+    fn parse_value(&mut self) -> Result<MarkerValue, io::Error> {
+        match self.next() {
+            Some(MarkerToken::Variable(variable)) => Ok(MarkerValue::Variable(variable)),
+            Some(MarkerToken::Literal(literal)) => Ok(MarkerValue::Literal(literal)),
+            _ => Err(invalid_marker(self.original, "expected variable or string")),
+        }
+    }
+}
+
+/// Values of the environment marker variables of the inspected environment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarkerEnvironment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_python_implementation: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+    pub sys_platform: String,
+    /// Extra that is being installed, empty for default requirements.
+    pub extra: String,
+}
+
+impl MarkerEnvironment {
+    /// Returns new instance of MarkerEnvironment built from conda record of installed `python` package.
+    /// Platform variables are derived from `subdir` of the record
+    /// (or from the current platform if record or its `subdir` is missing).
+    pub fn from_python_record(python: Option<&CondaRecord>) -> Self {
+        let subdir = python
+            .and_then(|r| r.subdir.clone())
+            .unwrap_or_else(current_subdir);
+        let (os, arch) = subdir.split_once('-').unwrap_or((subdir.as_str(), ""));
+        let (os_name, sys_platform, platform_system) = match os {
+            "win" => ("nt", "win32", "Windows"),
+            "osx" => ("posix", "darwin", "Darwin"),
+            "linux" => ("posix", "linux", "Linux"),
+            _ => ("", "", ""),
+        };
+        let platform_machine = match (os, arch) {
+            ("win", "64") => "AMD64",
+            ("win", "32") => "x86",
+            (_, "64") => "x86_64",
+            (_, "32") => "i686",
+            ("osx", "arm64") => "arm64",
+            (_, arch) => arch,
+        };
+
+        let python_full_version = python.map(|r| r.version.clone()).unwrap_or_default();
+        let python_version = python_full_version
+            .split('.')
+            .take(2)
+            .collect::<Vec<&str>>()
+            .join(".");
+        // conda builds of PyPy are marked with `pypy` in the build string of python package
+        let (implementation_name, platform_python_implementation) =
+            match python.map(|r| r.build.contains("pypy")) {
+                Some(true) => ("pypy", "PyPy"),
+                Some(false) => ("cpython", "CPython"),
+                None => ("", ""),
+            };
+        let implementation_version = if implementation_name == "cpython" {
+            python_full_version.clone()
+        } else {
+            String::new()
+        };
+
+        Self {
+            implementation_name: implementation_name.to_string(),
+            implementation_version,
+            os_name: os_name.to_string(),
+            platform_machine: platform_machine.to_string(),
+            platform_python_implementation: platform_python_implementation.to_string(),
+            platform_release: String::new(),
+            platform_system: platform_system.to_string(),
+            platform_version: String::new(),
+            python_full_version,
+            python_version,
+            sys_platform: sys_platform.to_string(),
+            extra: String::new(),
+        }
+    }
+
+    /// Returns copy of the environment with given extra being installed.
+    pub fn with_extra<T: AsRef<str>>(&self, extra: T) -> Self {
+        Self {
+            extra: extra.as_ref().to_string(),
+            ..self.clone()
+        }
+    }
+
+    #[doc(hidden)]
+    /// Returns value of given marker variable or literal.
+    fn resolve(&self, value: &MarkerValue) -> String {
+        let variable = match value {
+            MarkerValue::Literal(literal) => return literal.clone(),
+            MarkerValue::Variable(variable) => variable.as_str(),
+        };
+        match variable {
+            "implementation_name" => self.implementation_name.clone(),
+            "implementation_version" => self.implementation_version.clone(),
+            "os_name" | "os.name" => self.os_name.clone(),
+            "platform_machine" | "platform.machine" => self.platform_machine.clone(),
+            "platform_python_implementation"
+            | "platform.python_implementation"
+            | "python_implementation" => self.platform_python_implementation.clone(),
+            "platform_release" => self.platform_release.clone(),
+            "platform_system" => self.platform_system.clone(),
+            "platform_version" | "platform.version" => self.platform_version.clone(),
+            "python_full_version" => self.python_full_version.clone(),
+            "python_version" => self.python_version.clone(),
+            "sys_platform" | "sys.platform" => self.sys_platform.clone(),
+            "extra" => self.extra.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[doc(hidden)]
+/// Returns conda subdir of the platform conda-leaves runs on, e.g. `linux-64`.
+fn current_subdir() -> String {
+    let os = match std::env::consts::OS {
+        "windows" => "win",
+        "macos" => "osx",
+        os => os,
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "64",
+        "x86" => "32",
+        "aarch64" if os == "osx" => "arm64",
+        arch => arch,
+    };
+    format!("{}-{}", os, arch)
+}
+
+#[doc(hidden)]
+/// Normalizes name of the extra, e.g. `Foo_Bar` becomes `foo-bar`.
+fn normalize_extra(extra: String) -> String {
+    extra
+        .to_lowercase()
+        .split(['-', '_', '.'])
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+#[doc(hidden)]
+/// Compares values of marker expression the same way `packaging` does it:
+/// as PEP 440 versions if both sides are valid versions, as strings otherwise.
+fn compare_marker_values(left: &str, operator: &MarkerOperator, right: &str) -> bool {
+    match operator {
+        MarkerOperator::In => return right.contains(left),
+        MarkerOperator::NotIn => return !right.contains(left),
+        _ => {}
+    }
+    let specifier = Specifier::parse(format!("{}{}", operator, right));
+    if let (Ok(specifier), Ok(version)) = (specifier, Pep440Version::parse(left)) {
+        return specifier.contains(&version);
+    }
+    match operator {
+        MarkerOperator::Equal | MarkerOperator::Arbitrary => left == right,
+        MarkerOperator::NotEqual => left != right,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_only() {
+        // given:
+        let expected_requirement = Requirement {
+            name: String::from("six"),
+            extras: vec![],
+            specifier: SpecifierSet::default(),
+            url: None,
+            marker: None,
+        };
+        // when:
+        let requirement = Requirement::parse("six").unwrap();
+        // then:
+        assert_eq!(requirement, expected_requirement)
+    }
+
+    #[test]
+    fn test_parse_specifier_in_parentheses() {
+        // given:
+        let expected_requirement = Requirement {
+            name: String::from("lazy-object-proxy"),
+            extras: vec![],
+            specifier: SpecifierSet::parse("==1.4.*").unwrap(),
+            url: None,
+            marker: None,
+        };
+        // when:
+        let requirement = Requirement::parse("lazy-object-proxy (==1.4.*)").unwrap();
+        // then:
+        assert_eq!(requirement, expected_requirement)
+    }
+
+    #[test]
+    fn test_parse_specifier_without_parentheses() {
+        let requirement = Requirement::parse("foo>=1").unwrap();
+        assert_eq!(requirement.name, String::from("foo"));
+        assert_eq!(requirement.specifier, SpecifierSet::parse(">=1").unwrap())
+    }
+
+    #[test]
+    fn test_parse_legacy_version_only() {
+        let requirement = Requirement::parse("pkg2 (0.0.1)").unwrap();
+        assert_eq!(
+            requirement.specifier,
+            SpecifierSet::parse("==0.0.1").unwrap()
+        )
+    }
+
+    #[test]
+    fn test_parse_full() {
+        // given:
+        let string = r#"requests [security, tests] (>=2.8.1,==2.8.*) ; python_version < "2.7""#;
+        let expected_requirement = Requirement {
+            name: String::from("requests"),
+            extras: vec![String::from("security"), String::from("tests")],
+            specifier: SpecifierSet::parse(">=2.8.1,==2.8.*").unwrap(),
+            url: None,
+            marker: Some(Marker::Expression(
+                MarkerValue::Variable(String::from("python_version")),
+                MarkerOperator::Less,
+                MarkerValue::Literal(String::from("2.7")),
+            )),
+        };
+        // when:
+        let requirement = Requirement::parse(string).unwrap();
+        // then:
+        assert_eq!(requirement, expected_requirement);
+        assert_eq!(
+            requirement.to_string(),
+            r#"requests[security,tests]>=2.8.1,==2.8.*; python_version < "2.7""#
+        )
+    }
+
+    #[test]
+    fn test_parse_url() {
+        // given:
+        let string =
+            "pip @ https://github.com/pypa/pip/archive/1.3.1.zip#sha1=da9234ee ; extra == 'dev'";
+        // when:
+        let requirement = Requirement::parse(string).unwrap();
+        // then:
+        assert_eq!(requirement.name, String::from("pip"));
+        assert_eq!(
+            requirement.url,
+            Some(String::from(
+                "https://github.com/pypa/pip/archive/1.3.1.zip#sha1=da9234ee"
+            ))
+        );
+        assert_eq!(
+            requirement.marker,
+            Some(Marker::parse("extra == 'dev'").unwrap())
+        )
+    }
+
+    #[test]
+    fn test_parse_marker_precedence() {
+        // given:
+        let string = r#"os_name == "a" or python_version < "3.8" and extra == 'test'"#;
+        let expected_marker = Marker::Or(vec![
+            Marker::parse(r#"os_name == "a""#).unwrap(),
+            Marker::And(vec![
+                Marker::parse(r#"python_version < "3.8""#).unwrap(),
+                Marker::parse(r#"extra == "test""#).unwrap(),
+            ]),
+        ]);
+        // when:
+        let marker = Marker::parse(string).unwrap();
+        // then:
+        assert_eq!(marker, expected_marker)
+    }
+
+    #[test]
+    fn test_parse_marker_parentheses_and_in() {
+        // given:
+        let string = r#"(sys_platform == "win32" or sys_platform == "cygwin") and "arm" not in platform_machine"#;
+        // when:
+        let marker = Marker::parse(string).unwrap();
+        // then:
+        assert_eq!(
+            marker.to_string(),
+            r#"(sys_platform == "win32" or sys_platform == "cygwin") and "arm" not in platform_machine"#
+        )
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let invalid_requirements = vec![
+            "",
+            ">=1.0",
+            "foo[bar",
+            "foo (>=1.0",
+            "foo >=1.0 extra",
+            "foo @",
+            "foo; unknown_var == '1'",
+            "foo; python_version <",
+            "foo; python_version < '3.8",
+            "foo; (python_version < '3.8'",
+            "foo; python_version not '3.8'",
+        ];
+        for requirement in invalid_requirements {
+            assert!(
+                Requirement::parse(requirement).is_err(),
+                "'{}' should be invalid",
+                requirement
+            )
+        }
+    }
+
+    fn linux_py38() -> MarkerEnvironment {
+        let python = CondaRecord {
+            name: String::from("python"),
+            version: String::from("3.8.5"),
+            build: String::from("h7579374_1"),
+            subdir: Some(String::from("linux-64")),
+            ..CondaRecord::default()
+        };
+        MarkerEnvironment::from_python_record(Some(&python))
+    }
+
+    #[test]
+    fn test_from_python_record() {
+        // given:
+        let expected_environment = MarkerEnvironment {
+            implementation_name: String::from("cpython"),
+            implementation_version: String::from("3.8.5"),
+            os_name: String::from("posix"),
+            platform_machine: String::from("x86_64"),
+            platform_python_implementation: String::from("CPython"),
+            platform_release: String::new(),
+            platform_system: String::from("Linux"),
+            platform_version: String::new(),
+            python_full_version: String::from("3.8.5"),
+            python_version: String::from("3.8"),
+            sys_platform: String::from("linux"),
+            extra: String::new(),
+        };
+        // when:
+        let environment = linux_py38();
+        // then:
+        assert_eq!(environment, expected_environment)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        // given:
+        let environment = linux_py38();
+        let markers = vec![
+            (r#"python_version < "3.7""#, false),
+            (r#"python_version < "3.10""#, true),
+            (r#"python_full_version >= "3.8.1""#, true),
+            (r#"sys_platform == "win32""#, false),
+            (r#"sys_platform != "win32""#, true),
+            (
+                r#"implementation_name == "cpython" and python_version < "3.8""#,
+                false,
+            ),
+            (r#"os_name == "nt" or platform_machine == "x86_64""#, true),
+            (r#""linux" in sys_platform"#, true),
+            (r#""arm" not in platform_machine"#, true),
+            (r#"platform.python_implementation == 'CPython'"#, true),
+            (r#"extra == "test""#, false),
+            (r#"extra != "test""#, true),
+            (r#"platform_system > "A""#, false),
+        ];
+        for (marker, expected) in markers {
+            // when:
+            let result = Marker::parse(marker).unwrap().evaluate(&environment);
+            // then:
+            assert_eq!(result, expected, "'{}'", marker)
+        }
+    }
+
+    #[test]
+    fn test_evaluate_with_extra() {
+        // given:
+        let environment = linux_py38().with_extra("Dev_Tools");
+        // when:
+        let marker = Marker::parse(r#"extra == "dev-tools" and python_version >= "3.6""#).unwrap();
+        // then:
+        assert!(marker.evaluate(&environment));
+        assert!(!marker.evaluate(&linux_py38()))
+    }
+}
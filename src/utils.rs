@@ -1,58 +1,103 @@
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
 
 use lazy_static::lazy_static;
 use rayon::prelude::*;
 use regex::Regex;
 
+use crate::filter::{get_filter_policy, FilterPolicy};
+use crate::graph::DependencyGraph;
+use crate::marks::{apply_marks, get_marks};
 use crate::metadata::Metadata;
+use crate::package::Installer;
+use crate::pep440::Pep440Version;
+use crate::pep508::MarkerEnvironment;
 
-#[doc(hidden)]
-pub(crate) fn split_and_take_n_elem<T: AsRef<str>>(string: &T, n: usize) -> Option<&str> {
-    if string.as_ref().is_empty() {
-        return None;
-    }
-    let v = string.as_ref().split(" ").collect::<Vec<_>>();
-    let v = *v.get(n).unwrap();
-    Some(v)
+lazy_static! {
+    #[doc(hidden)]
+    static ref NAME_SEPARATORS_REGEX: Regex = Regex::new(r"[-_.]+").unwrap();
 }
 
 lazy_static! {
     #[doc(hidden)]
-    static ref VERSION_REGEX: Regex =
-        Regex::new(r"(?P<major>\d+)\.(?P<minor>\d+)\.(?P<patch>\d+)").unwrap();
+    pub(crate) static ref CONDA_METADATA: HashMap<String, Metadata> = get_conda_metadata();
 }
 
 lazy_static! {
     #[doc(hidden)]
-    pub(crate) static ref CONDA_METADATA: HashMap<String, Metadata> = get_conda_metadata();
+    /// Environment markers of the inspected environment, derived from installed `python` package.
+    pub(crate) static ref MARKER_ENVIRONMENT: MarkerEnvironment = MarkerEnvironment::from_python_record(
+        CONDA_METADATA.get("python").and_then(|m| m.record.as_ref())
+    );
 }
 
-#[doc(hidden)]
-/// Returns optional version (as a String) from given text, when it gets a match again version regex.
-fn extract_version<T: AsRef<str>>(text: T) -> Option<String> {
-    match VERSION_REGEX.find(text.as_ref()) {
-        Some(v) => return Some(v.as_str().to_string()),
-        None => return None,
-    };
+/// Returns canonical form of the package name (PEP 503): lowercase, with runs of `-`, `_` and `.` replaced by `-`,
+/// e.g. `PyYAML` -> `pyyaml`, `ruamel.yaml` -> `ruamel-yaml`.
+pub fn canonicalize_name<T: AsRef<str>>(name: T) -> String {
+    NAME_SEPARATORS_REGEX
+        .replace_all(name.as_ref(), "-")
+        .to_lowercase()
+}
+
+/// Returns metadata of the package with given name.
+/// Exact name is looked up first (conda names are exact), then PEP 503 canonical name,
+/// so `ruamel.yaml`, `ruamel_yaml` and `Ruamel-YAML` all resolve to the same package,
+/// and finally Python distributions provided by conda packages (see `Metadata::pypi_names`).
+pub(crate) fn find_metadata<T: AsRef<str>>(
+    metadata: &HashMap<String, Metadata>,
+    name: T,
+) -> Option<&Metadata> {
+    if let Some(m) = metadata.get(name.as_ref()) {
+        return Some(m);
+    }
+    let canonical = canonicalize_name(name);
+    metadata
+        .values()
+        .filter(|m| canonicalize_name(&m.name) == canonical)
+        .min_by(|a, b| a.name.cmp(&b.name))
+        .or_else(|| {
+            // conda package may provide Python distribution under different name (e.g. `pytorch` provides `torch`)
+            metadata
+                .values()
+                .filter(|m| m.pypi_names.contains(&canonical))
+                .min_by(|a, b| a.name.cmp(&b.name))
+        })
+}
+
+/// Returns metadata of the package that satisfies conda dependency (or constraint) on package with given name:
+/// conda package with exact name, or Python distribution installed by pip on top of it,
+/// that may spell the name differently (e.g. `PyYAML` over conda `pyyaml`).
+pub(crate) fn find_conda_dependency<T: AsRef<str>>(
+    metadata: &HashMap<String, Metadata>,
+    name: T,
+) -> Option<&Metadata> {
+    if let Some(m) = metadata.get(name.as_ref()) {
+        return Some(m);
+    }
+    let canonical = canonicalize_name(name);
+    metadata
+        .values()
+        .filter(|m| m.record.is_none() && canonicalize_name(&m.name) == canonical)
+        .min_by(|a, b| a.name.cmp(&b.name))
 }
 
 #[doc(hidden)]
 /// Returns CONDA_PREFIX evironment variable.
 ///
 /// Panics if CONDA_PREFIX is not specified.
-fn get_conda_prefix() -> String {
+pub(crate) fn get_conda_prefix() -> String {
     match std::env::var("CONDA_PREFIX") {
-        Ok(var) => return var,
-        Err(e) => panic!(e.to_string()),
+        Ok(var) => var,
+        Err(e) => panic!("{}", e),
     }
 }
 
 /// Returns `conda-meta` path for activated conda environment.
 pub(crate) fn get_conda_meta_path() -> PathBuf {
     let conda_prefix = get_conda_prefix();
-    let conda_meta = Path::new(&conda_prefix).join("conda-meta");
-    conda_meta
+    Path::new(&conda_prefix).join("conda-meta")
 }
 
 /// Returns the dictionary of all installed Python packages with environment, by reading all available metadata files.
@@ -65,56 +110,316 @@ pub(crate) fn get_conda_meta_path() -> PathBuf {
 /// Output of the function is assigned to `CONDA_METADATA` static variable using `lazy_static!`.
 /// It's been designed like that to avoid multiple IO operations, the result is been generated once, and reused.
 pub(crate) fn get_conda_metadata() -> HashMap<String, Metadata> {
-    let conda_meta = get_conda_meta_path();
+    get_prefix_metadata(get_conda_prefix())
+}
+
+#[doc(hidden)]
+/// Returns metadata of all packages installed in environment with given prefix,
+/// both conda packages (from `conda-meta`) and packages installed by pip (from `site-packages`).
+pub(crate) fn get_prefix_metadata<P: AsRef<Path>>(prefix: P) -> HashMap<String, Metadata> {
+    let mut conda_metadata = get_conda_meta_metadata(&prefix);
+    apply_pypi_mapping(&mut conda_metadata, get_pypi_mapping_overrides());
+
+    // packages installed by pip on top of conda packages take precedence,
+    // even if they spell the name differently (e.g. `PyYAML` over conda `pyyaml`)
+    let pip_metadata = get_pip_metadata(prefix, &conda_metadata);
+    let pip_names: HashSet<String> = pip_metadata
+        .iter()
+        .map(|m| canonicalize_name(&m.name))
+        .collect();
+    conda_metadata.retain(|name, _| !pip_names.contains(&canonicalize_name(name)));
+    conda_metadata.extend(pip_metadata.into_iter().map(|m| (m.name.clone(), m)));
+
+    conda_metadata
+}
+
+#[doc(hidden)]
+/// Returns metadata of conda packages installed in environment with given prefix (from `conda-meta`),
+/// including packages that were later shadowed by packages installed by pip.
+pub(crate) fn get_conda_meta_metadata<P: AsRef<Path>>(prefix: P) -> HashMap<String, Metadata> {
+    let conda_meta = prefix.as_ref().join("conda-meta");
 
     // read conda meta directory and get all of the json metadata files
-    let json_metadata_files: Vec<_> = conda_meta
-        .read_dir()
-        .unwrap()
-        .map(|direntry| direntry.unwrap().path())
-        .filter(|path| path.is_file() & path.to_str().unwrap().ends_with(".json"))
+    let entries = match conda_meta.read_dir() {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Cannot read '{}': {}", conda_meta.display(), e);
+            return HashMap::new();
+        }
+    };
+    let json_metadata_files: Vec<_> = entries
+        .filter_map(|direntry| direntry.ok().map(|d| d.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "json"))
         .collect();
 
     // iterate over json files and create hashmap of all packages installed
-    let conda_metadata: HashMap<String, Metadata> = json_metadata_files
+    // (records that cannot be read are skipped, so they don't take down every command)
+    json_metadata_files
         .par_iter()
-        .map(|path| {
-            let metadata = Metadata::from_json(&path).unwrap();
-            (metadata.name.clone(), metadata)
+        .filter_map(|path| match Metadata::from_json(&path) {
+            Ok(metadata) => Some((metadata.name.clone(), metadata)),
+            Err(e) => {
+                log::warn!("Skipping '{}': {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[doc(hidden)]
+static PYPI_MAPPING: OnceLock<HashMap<String, Vec<String>>> = OnceLock::new();
+
+/// Sets conda -> PyPI name mapping supplied by the user (see `read_pypi_mapping`),
+/// which takes precedence over Python distributions inferred for conda packages.
+/// It has to be called before any package is read, later calls have no effect.
+pub fn set_pypi_mapping(mapping: HashMap<String, Vec<String>>) {
+    let _ = PYPI_MAPPING.set(mapping);
+}
+
+#[doc(hidden)]
+/// Returns conda -> PyPI name mapping supplied by the user, or an empty mapping if none has been set.
+fn get_pypi_mapping_overrides() -> &'static HashMap<String, Vec<String>> {
+    PYPI_MAPPING.get_or_init(HashMap::new)
+}
+
+/// Reads conda -> PyPI name mapping from json file, where keys are names of conda packages
+/// and values are names of Python distributions they provide (single name or list of names),
+/// e.g. `{"py-opencv": "opencv-python", "pytorch": ["torch"]}`.
+///
+/// If the file cannot be read or has unexpected format, then an error naming the file is returned.
+pub(crate) fn read_pypi_mapping<P: AsRef<Path>>(
+    path: P,
+) -> Result<HashMap<String, Vec<String>>, io::Error> {
+    parse_pypi_mapping(path.as_ref()).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "Invalid PyPI mapping file '{}': {}",
+                path.as_ref().display(),
+                e
+            ),
+        )
+    })
+}
+
+#[doc(hidden)]
+fn parse_pypi_mapping(path: &Path) -> Result<HashMap<String, Vec<String>>, io::Error> {
+    let file = std::fs::File::open(path)?;
+    let value: serde_json::Value = serde_json::from_reader(io::BufReader::new(file))?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let object = value
+        .as_object()
+        .ok_or_else(|| invalid("expected object of conda names"))?;
+    object
+        .iter()
+        .map(|(conda_name, pypi_names)| {
+            let pypi_names = match pypi_names {
+                serde_json::Value::String(name) => vec![name.clone()],
+                serde_json::Value::Array(names) => names
+                    .iter()
+                    .map(|n| n.as_str().map(String::from))
+                    .collect::<Option<Vec<String>>>()
+                    .ok_or_else(|| invalid("expected list of PyPI names"))?,
+                _ => return Err(invalid("expected PyPI name or list of PyPI names")),
+            };
+            Ok((conda_name.clone(), pypi_names))
+        })
+        .collect()
+}
+
+#[doc(hidden)]
+/// Replaces Python distributions inferred for conda packages with ones given by the user.
+fn apply_pypi_mapping(
+    conda_metadata: &mut HashMap<String, Metadata>,
+    overrides: &HashMap<String, Vec<String>>,
+) {
+    for (conda_name, pypi_names) in overrides.iter() {
+        if let Some(m) = conda_metadata.get_mut(conda_name) {
+            m.pypi_names = pypi_names.iter().map(canonicalize_name).collect();
+            m.pypi_names.sort();
+            m.pypi_names.dedup();
+        }
+    }
+}
+
+/// Returns `site-packages` directories of environment with given prefix,
+/// `lib/pythonX.Y/site-packages` on Unix and `Lib/site-packages` on Windows.
+pub(crate) fn get_site_packages_paths<P: AsRef<Path>>(prefix: P) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = prefix
+        .as_ref()
+        .join("lib")
+        .read_dir()
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_name().to_string_lossy().starts_with("python"))
+                .map(|e| e.path().join("site-packages"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.push(prefix.as_ref().join("Lib").join("site-packages"));
+    paths.retain(|p| p.is_dir());
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Returns metadata of Python packages from `site-packages` (`*.dist-info` and `*.egg-info`)
+/// that are not owned by any of given conda packages, so they must have been installed by pip.
+///
+/// Distributions with unreadable metadata are skipped with a warning.
+pub(crate) fn get_pip_metadata<P: AsRef<Path>>(
+    prefix: P,
+    conda_metadata: &HashMap<String, Metadata>,
+) -> Vec<Metadata> {
+    let prefix = prefix.as_ref();
+    let owned = get_conda_owned_distributions(conda_metadata);
+
+    let distributions: Vec<PathBuf> = get_site_packages_paths(prefix)
+        .iter()
+        .filter_map(|site_packages| site_packages.read_dir().ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()))
+        .filter(|path| {
+            let extension = path.extension().and_then(|e| e.to_str());
+            extension == Some("dist-info") || extension == Some("egg-info")
+        })
+        .filter(|path| !owned.contains(&relative_path(prefix, path)))
+        .collect();
+
+    let mut pip_metadata: Vec<Metadata> = distributions
+        .par_iter()
+        .filter_map(|path| {
+            let metadata = match path.extension().and_then(|e| e.to_str()) {
+                Some("dist-info") => Metadata::from_dist_info(path),
+                _ => Metadata::from_egg_info(path),
+            };
+            metadata
+                .map_err(|e| log::warn!("Skipping '{}': {}", path.display(), e))
+                .ok()
         })
         .collect();
+    pip_metadata.sort_by(|a, b| a.name.cmp(&b.name));
+    pip_metadata
+}
 
+#[doc(hidden)]
+/// Returns paths of `*.dist-info` and `*.egg-info` distributions (relative to the prefix)
+/// that were installed by conda packages.
+fn get_conda_owned_distributions(conda_metadata: &HashMap<String, Metadata>) -> HashSet<String> {
     conda_metadata
+        .values()
+        .filter_map(|m| m.record.as_ref())
+        .flat_map(|r| r.files.iter())
+        .filter_map(|file| {
+            let components: Vec<&str> = file.split('/').collect();
+            components
+                .iter()
+                .position(|c| c.ends_with(".dist-info") || c.ends_with(".egg-info"))
+                .map(|idx| components[..=idx].join("/"))
+        })
+        .collect()
 }
 
-/// Returns a list of dependencies for given package.
-pub fn get_dependent_packages<T: AsRef<str>>(name: T) -> Option<Vec<String>> {
-    match CONDA_METADATA.get(name.as_ref()) {
-        Some(_) => (),
-        None => return None,
+#[doc(hidden)]
+/// Returns path relative to the prefix, with `/` used as a separator (the way conda records it).
+pub(crate) fn relative_path(prefix: &Path, path: &Path) -> String {
+    let prefix = normalize_path(prefix);
+    let path = normalize_path(path);
+    let relative = path.strip_prefix(&prefix).unwrap_or(&path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[doc(hidden)]
+/// Resolves `.` and `..` components of given path, without touching the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..")
+                }
+            }
+            component => normalized.push(component),
+        }
     }
+    normalized
+}
 
-    let dependent_packages: Vec<String> = CONDA_METADATA
-        .values()
-        .filter(|m| m.requires_dist.contains(&name.as_ref().to_owned()))
-        .map(|m| m.name.clone())
-        .filter(|n| !n.starts_with("python"))
+/// Returns a list of dependencies for given package.
+/// If `no_pip` is set, packages installed by pip are not taken into account.
+/// Packages filtered out by the policy (see `FilterPolicy`) are not reported.
+pub fn get_dependent_packages<T: AsRef<str>>(name: T, no_pip: bool) -> Option<Vec<String>> {
+    dependent_packages(&CONDA_METADATA, name, no_pip, get_filter_policy())
+}
+
+#[doc(hidden)]
+fn dependent_packages<T: AsRef<str>>(
+    metadata: &HashMap<String, Metadata>,
+    name: T,
+    no_pip: bool,
+    policy: &FilterPolicy,
+) -> Option<Vec<String>> {
+    let package = find_metadata(metadata, name)?;
+    let graph = DependencyGraph::new(metadata.values());
+
+    let dependent_packages: Vec<String> = dependents(&graph, &package.name, no_pip)
+        .filter(|n| !policy.is_excluded(n))
         .collect();
     Some(dependent_packages)
 }
 
+#[doc(hidden)]
+/// Returns names of packages that depend on the package with given name.
+/// If `no_pip` is set, packages installed by pip are not taken into account.
+pub(crate) fn dependents<'a>(
+    graph: &'a DependencyGraph,
+    name: &str,
+    no_pip: bool,
+) -> impl Iterator<Item = String> + 'a {
+    graph
+        .dependents(name)
+        .iter()
+        .filter(move |m| !(no_pip && m.installer() != Installer::Conda))
+        .map(|m| m.name.clone())
+}
+
 /// Returns a list of packages that are not defined as a dependency for any other package in the environment.
-pub fn get_leaves() -> Vec<String> {
+/// If `no_pip` is set, packages installed by pip are not taken into account.
+/// Packages filtered out by the policy (see `FilterPolicy`) are not reported.
+/// Marks of the user (see `Marks`) take precedence over the dependency graph.
+pub fn get_leaves(no_pip: bool) -> Vec<String> {
+    apply_marks(
+        leaves(&CONDA_METADATA, no_pip, get_filter_policy()),
+        &CONDA_METADATA,
+        no_pip,
+        &get_marks(),
+    )
+}
+
+#[doc(hidden)]
+pub(crate) fn leaves(
+    metadata: &HashMap<String, Metadata>,
+    no_pip: bool,
+    policy: &FilterPolicy,
+) -> Vec<String> {
     // filtering
     // 1. packages that are not dependend on any other packages
+    //    (filtered out packages count as dependent as well, e.g. `openssl` required by `python` is not a leaf)
     // skipping
-    // 1. packages that starts with `lib`
-    // 2. packages that starts with `_` (underscore), they are really low level
-    // I can simply unwrap `get_dependent_packages` because I loop through CONDA_METADATA
-    let mut leaves: Vec<String> = CONDA_METADATA
-        .keys()
-        .filter(|name| get_dependent_packages(name).unwrap().len() == 0)
-        .filter(|name| !(name.starts_with("lib") || name.starts_with("_")))
+    // 1. packages filtered out by the policy
+    let graph = DependencyGraph::new(metadata.values());
+    let mut leaves: Vec<String> = metadata
+        .values()
+        .filter(|m| !(no_pip && m.installer() != Installer::Conda))
+        .map(|m| &m.name)
+        .filter(|name| dependents(&graph, name, no_pip).next().is_none())
+        .filter(|name| !policy.is_excluded(name))
         .map(|name| name.to_string())
         .collect();
     // sort vector
@@ -125,35 +430,131 @@ pub fn get_leaves() -> Vec<String> {
     leaves
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Dependency of an installed package that is not satisfied by packages installed in the environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsatisfiedDependency {
+    /// Name of the package that defines the dependency.
+    pub package: String,
+    /// Name of the dependency.
+    pub dependency: String,
+    /// Spec of the dependency.
+    pub spec: String,
+    /// Installed version (and build) of the dependency, or `None` if dependency is not installed at all.
+    pub installed: Option<String>,
+}
 
-    #[test]
-    fn test_split_and_take_n_elem() {
-        let input = "some_pkg (v.1.1.1)";
-        assert_eq!(split_and_take_n_elem(&input, 0), Some("some_pkg"));
-        assert_eq!(split_and_take_n_elem(&input, 1), Some("(v.1.1.1)"));
-    }
+/// Returns a list of dependencies that are not satisfied by packages installed in the environment.
+/// Conda `depends` are checked against MatchSpecs, Python `Requires-Dist` against PEP 440 specifiers.
+pub fn get_unsatisfied_dependencies() -> Vec<UnsatisfiedDependency> {
+    unsatisfied_dependencies(&CONDA_METADATA)
+}
 
-    #[test]
-    fn test_split_and_take_n_elem_empty_string() {
-        let input = "";
-        assert_eq!(split_and_take_n_elem(&input, 0), None);
-        assert_eq!(split_and_take_n_elem(&input, 1), None);
-    }
+#[doc(hidden)]
+fn unsatisfied_dependencies(metadata: &HashMap<String, Metadata>) -> Vec<UnsatisfiedDependency> {
+    // all dependencies are checked, the filter policy only decides what is displayed as a tree or leaf
+    let conda_unsatisfied = metadata
+        .values()
+        .flat_map(|m| m.record_depends().into_iter().map(move |spec| (m, spec)))
+        .filter_map(
+            |(m, spec)| match find_conda_dependency(metadata, &spec.name) {
+                Some(installed) => match &installed.record {
+                    Some(record) if !spec.matches(record) => Some(UnsatisfiedDependency {
+                        package: m.name.clone(),
+                        dependency: spec.name.clone(),
+                        spec: spec.to_string(),
+                        installed: Some(
+                            format!("{} {}", record.version, record.build)
+                                .trim()
+                                .to_string(),
+                        ),
+                    }),
+                    _ => None,
+                },
+                None => Some(UnsatisfiedDependency {
+                    package: m.name.clone(),
+                    dependency: spec.name.clone(),
+                    spec: spec.to_string(),
+                    installed: None,
+                }),
+            },
+        );
+    let pip_unsatisfied = metadata
+        .values()
+        .flat_map(|m| m.applicable_requires_dist().map(move |req| (m, req)))
+        .filter_map(|(m, req)| match find_metadata(metadata, &req.name) {
+            Some(installed) => match Pep440Version::parse(&installed.version) {
+                Ok(version) if !req.specifier.contains(&version) => Some(UnsatisfiedDependency {
+                    package: m.name.clone(),
+                    dependency: req.name.clone(),
+                    spec: req.to_string(),
+                    installed: Some(installed.version.clone()),
+                }),
+                _ => None,
+            },
+            None => Some(UnsatisfiedDependency {
+                package: m.name.clone(),
+                dependency: req.name.clone(),
+                spec: req.to_string(),
+                installed: None,
+            }),
+        });
+    let mut unsatisfied: Vec<UnsatisfiedDependency> =
+        conda_unsatisfied.chain(pip_unsatisfied).collect();
+    unsatisfied.sort_by(|a, b| (&a.package, &a.dependency).cmp(&(&b.package, &b.dependency)));
+    unsatisfied
+}
 
-    #[test]
-    fn test_extract_version() {
-        let output = extract_version("version 3.7.3");
-        assert_eq!(output, Some("3.7.3".to_string()));
-    }
+/// Constraint (`constrains` of conda record) violated by a package installed in the environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViolatedConstraint {
+    /// Name of the package that defines the constraint.
+    pub package: String,
+    /// Name of the constrained package.
+    pub constrained: String,
+    /// Spec of the constraint.
+    pub spec: String,
+    /// Installed version and build of the constrained package.
+    pub installed: String,
+}
 
-    #[test]
-    fn test_extract_version_empty() {
-        let output = extract_version("version");
-        assert_eq!(output, None);
-    }
+/// Returns a list of constraints (`constrains` of conda records) violated by packages installed in the environment.
+/// Constraints on packages that are not installed are satisfied by definition.
+pub fn get_violated_constraints() -> Vec<ViolatedConstraint> {
+    violated_constraints(&CONDA_METADATA)
+}
+
+#[doc(hidden)]
+fn violated_constraints(metadata: &HashMap<String, Metadata>) -> Vec<ViolatedConstraint> {
+    let mut violated: Vec<ViolatedConstraint> = metadata
+        .values()
+        .flat_map(|m| m.constrains().into_iter().map(move |spec| (m, spec)))
+        .filter_map(|(m, spec)| {
+            let record = find_conda_dependency(metadata, &spec.name)?
+                .record
+                .as_ref()?;
+            match spec.matches(record) {
+                true => None,
+                false => Some(ViolatedConstraint {
+                    package: m.name.clone(),
+                    constrained: spec.name.clone(),
+                    spec: spec.to_string(),
+                    installed: format!("{} {}", record.version, record.build)
+                        .trim()
+                        .to_string(),
+                }),
+            }
+        })
+        .collect();
+    violated.sort_by(|a, b| (&a.package, &a.constrained).cmp(&(&b.package, &b.constrained)));
+    violated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marks::{Mark, Marks, MARKS_FILENAME};
+    use crate::matchspec::MatchSpec;
+    use crate::metadata::CondaRecord;
 
     #[test]
     fn test_get_conda_prefix() {
@@ -190,13 +591,31 @@ mod tests {
     fn test_get_conda_metadata() {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let record = |name: &str, depends: Vec<&str>| CondaRecord {
+            name: String::from(name),
+            version: String::from("0.0.1"),
+            channel: Some(String::from("conda-forge")),
+            depends: depends.into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
         let mut expected_conda_metadata: HashMap<String, Metadata> = HashMap::new();
         expected_conda_metadata.insert(
             String::from("pkg1"),
             Metadata {
                 name: String::from("pkg1"),
                 version: String::from("0.0.1"),
+                depends: vec![],
                 requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Metadata::from_json("./tests/data/conda-meta/pkg1-0.0.1.json")
+                    .unwrap()
+                    .record,
+                pypi_names: vec![],
             },
         );
         expected_conda_metadata.insert(
@@ -204,7 +623,16 @@ mod tests {
             Metadata {
                 name: String::from("pkg2a"),
                 version: String::from("0.0.1"),
-                requires_dist: vec![String::from("pkg1")],
+                depends: vec![MatchSpec::name_only("pkg1", "pkg1 (0.0.1)")],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Some(record("pkg2a", vec!["pkg1 (0.0.1)"])),
+                pypi_names: vec![],
             },
         );
         expected_conda_metadata.insert(
@@ -212,7 +640,16 @@ mod tests {
             Metadata {
                 name: String::from("pkg2b"),
                 version: String::from("0.0.1"),
+                depends: vec![],
                 requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Some(record("pkg2b", vec![])),
+                pypi_names: vec![],
             },
         );
         expected_conda_metadata.insert(
@@ -220,7 +657,16 @@ mod tests {
             Metadata {
                 name: String::from("pkg2c"),
                 version: String::from("0.0.1"),
-                requires_dist: vec![String::from("pkg2a")],
+                depends: vec![MatchSpec::name_only("pkg2a", "pkg2a (0.0.1)")],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Some(record("pkg2c", vec!["pkg2a (0.0.1)"])),
+                pypi_names: vec![],
             },
         );
         expected_conda_metadata.insert(
@@ -228,7 +674,42 @@ mod tests {
             Metadata {
                 name: String::from("pkg3"),
                 version: String::from("0.0.1"),
-                requires_dist: vec![String::from("pkg2a"), String::from("pkg2b")],
+                depends: vec![
+                    MatchSpec::name_only("pkg2a", "pkg2a (0.0.1)"),
+                    MatchSpec::name_only("pkg2b", "pkg2b (0.0.1)"),
+                ],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Some(record("pkg3", vec!["pkg2a (0.0.1)", "pkg2b (0.0.1)"])),
+                pypi_names: vec![],
+            },
+        );
+        expected_conda_metadata.insert(
+            String::from("pkg3b"),
+            Metadata {
+                name: String::from("pkg3b"),
+                version: String::from("0.0.1"),
+                depends: vec![
+                    MatchSpec::parse("pkg2a >=0.0.1,<1.0a0").unwrap(),
+                    MatchSpec::parse("pkg2b 0.0.1.*").unwrap(),
+                ],
+                requires_dist: vec![],
+                provides_extra: vec![],
+                core_metadata: None,
+                installed_by: None,
+                direct_url: None,
+                record_files: vec![],
+                location: None,
+                record: Some(record(
+                    "pkg3b",
+                    vec!["pkg2a >=0.0.1,<1.0a0", "pkg2b 0.0.1.*"],
+                )),
+                pypi_names: vec![],
             },
         );
         // when:
@@ -242,7 +723,7 @@ mod tests {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
         // when:
-        let dependent_packages = get_dependent_packages(String::from("pkg404"));
+        let dependent_packages = get_dependent_packages(String::from("pkg404"), false);
         // then:
         assert_eq!(dependent_packages, None)
     }
@@ -253,7 +734,7 @@ mod tests {
         std::env::set_var("CONDA_PREFIX", "./tests/data");
         let expected_dependent_packages: Option<Vec<String>> = Some(vec![]);
         // when:
-        let dependent_packages = get_dependent_packages(String::from("pkg3"));
+        let dependent_packages = get_dependent_packages(String::from("pkg3"), false);
         // then:
         assert_eq!(dependent_packages, expected_dependent_packages)
     }
@@ -262,9 +743,9 @@ mod tests {
     fn test_get_dependent_packages_one() {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
-        let expected_dependent_packages = Some(vec![String::from("pkg3")]);
+        let expected_dependent_packages = Some(vec![String::from("pkg2a")]);
         // when:
-        let dependent_packages = get_dependent_packages(String::from("pkg2b"));
+        let dependent_packages = get_dependent_packages(String::from("pkg1"), false);
         // then:
         assert_eq!(dependent_packages, expected_dependent_packages)
     }
@@ -273,10 +754,14 @@ mod tests {
     fn test_get_dependent_packages_multiple() {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
-        let mut expected_dependent_packages = vec![String::from("pkg3"), String::from("pkg2c")];
+        let mut expected_dependent_packages = vec![
+            String::from("pkg3"),
+            String::from("pkg2c"),
+            String::from("pkg3b"),
+        ];
         expected_dependent_packages.sort();
         // when:
-        let mut dependent_packages = get_dependent_packages(String::from("pkg2a")).unwrap();
+        let mut dependent_packages = get_dependent_packages(String::from("pkg2a"), false).unwrap();
         dependent_packages.sort();
         // then:
         assert_eq!(dependent_packages, expected_dependent_packages)
@@ -286,11 +771,324 @@ mod tests {
     fn test_get_leaves() {
         // given:
         std::env::set_var("CONDA_PREFIX", "./tests/data");
-        let mut expected_leaves = vec![String::from("pkg2c"), String::from("pkg3")];
+        let mut expected_leaves = vec![
+            String::from("pkg2c"),
+            String::from("pkg3"),
+            String::from("pkg3b"),
+        ];
         expected_leaves.sort();
         // when:
-        let leaves = get_leaves();
+        let leaves = get_leaves(false);
         // then:
         assert_eq!(leaves, expected_leaves)
     }
+
+    #[test]
+    fn test_get_unsatisfied_dependencies() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        // when:
+        let unsatisfied = get_unsatisfied_dependencies();
+        // then:
+        assert_eq!(unsatisfied, vec![])
+    }
+
+    #[test]
+    fn test_leaves_shadowed_by_pip() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/shadowed-env");
+        // when:
+        let leaves = leaves(&metadata, false, &FilterPolicy::default());
+        let dependent = dependent_packages(&metadata, "pyyaml", false, &FilterPolicy::default());
+        // then:
+        // conda `yamlconf` depends on `pyyaml`, replaced by `PyYAML` installed by pip
+        assert!(metadata.contains_key("PyYAML"));
+        assert_eq!(leaves, vec!["yamlconf"]);
+        assert_eq!(dependent, Some(vec![String::from("yamlconf")]))
+    }
+
+    #[test]
+    fn test_unsatisfied_dependencies_filtered_out() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_conda_meta_metadata("./tests/data/pinned-env");
+        // when:
+        let unsatisfied = unsatisfied_dependencies(&metadata);
+        // then:
+        // `python` and `lib*` are filtered out by default, but they are still checked
+        assert_eq!(
+            unsatisfied,
+            vec![
+                UnsatisfiedDependency {
+                    package: String::from("pkg-py"),
+                    dependency: String::from("libfoo"),
+                    spec: String::from("libfoo >=1.0"),
+                    installed: None,
+                },
+                UnsatisfiedDependency {
+                    package: String::from("pkg-py"),
+                    dependency: String::from("python"),
+                    spec: String::from("python >=3.8,<3.9.0a0"),
+                    installed: Some(String::from("3.9.0 h2a148a8_4_cpython")),
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn test_unsatisfied_dependencies_shadowed_by_pip() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/shadowed-env");
+        // when:
+        let unsatisfied = unsatisfied_dependencies(&metadata);
+        // then:
+        assert_eq!(unsatisfied, vec![])
+    }
+
+    #[test]
+    fn test_get_violated_constraints() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        // when:
+        let violated = get_violated_constraints();
+        // then:
+        assert_eq!(violated, vec![])
+    }
+
+    #[test]
+    fn test_violated_constraints() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_conda_meta_metadata("./tests/data/constrained-env");
+        // when:
+        let violated = violated_constraints(&metadata);
+        // then:
+        // constraint on `pkg404` is satisfied, as it isn't installed
+        assert_eq!(
+            violated,
+            vec![ViolatedConstraint {
+                package: String::from("pkg-c"),
+                constrained: String::from("pkg1"),
+                spec: String::from("pkg1 >=1.0"),
+                installed: String::from("0.0.1 py38_0"),
+            }]
+        )
+    }
+
+    #[test]
+    fn test_get_site_packages_paths() {
+        // given:
+        let prefix = "./tests/data/mixed-env";
+        // when:
+        let paths = get_site_packages_paths(prefix);
+        // then:
+        assert_eq!(
+            paths,
+            vec![PathBuf::from(
+                "./tests/data/mixed-env/lib/python3.8/site-packages"
+            )]
+        )
+    }
+
+    #[test]
+    fn test_get_pip_metadata() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/mixed-env";
+        let conda_metadata: HashMap<String, Metadata> = vec![
+            "./tests/data/mixed-env/conda-meta/python-3.8.5-h7579374_1.json",
+            "./tests/data/mixed-env/conda-meta/six-1.15.0-py_0.json",
+        ]
+        .into_iter()
+        .map(|path| {
+            let m = Metadata::from_json(path).unwrap();
+            (m.name.clone(), m)
+        })
+        .collect();
+        // when:
+        let pip_metadata = get_pip_metadata(prefix, &conda_metadata);
+        // then:
+        let names: Vec<&str> = pip_metadata.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["astor", "legacy-pkg", "patsy"]);
+        assert!(pip_metadata.iter().all(|m| m.installer() == Installer::Pip));
+        assert_eq!(
+            pip_metadata[1].dependency_names(),
+            vec!["Astor"],
+            "requires.txt of egg-info should be taken into account"
+        )
+    }
+
+    #[test]
+    fn test_get_prefix_metadata_mixed_environment() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        // when:
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        // then:
+        let mut names: Vec<&String> = metadata.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["astor", "legacy-pkg", "patsy", "python", "six"]);
+        assert_eq!(metadata["six"].installer(), Installer::Conda);
+        assert_eq!(metadata["patsy"].installer(), Installer::Pip)
+    }
+
+    #[test]
+    fn test_leaves_mixed_environment() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        // when:
+        let all_leaves = leaves(&metadata, false, &FilterPolicy::default());
+        let conda_leaves = leaves(&metadata, true, &FilterPolicy::default());
+        // then:
+        assert_eq!(all_leaves, vec!["legacy-pkg", "patsy"]);
+        assert_eq!(conda_leaves, vec!["six"])
+    }
+
+    #[test]
+    fn test_get_conda_meta_metadata_with_marks() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/broken-env";
+        // when:
+        let metadata = get_conda_meta_metadata(prefix);
+        let marks = Marks::from_file(Path::new(prefix).join("conda-meta").join(MARKS_FILENAME));
+        // then:
+        let mut names: Vec<&String> = metadata.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["pkg-a", "pkg-b"]);
+        assert_eq!(marks.unwrap().get("pkg-b"), Some(Mark::Manual))
+    }
+
+    #[test]
+    fn test_leaves_filter_policy() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let policy = FilterPolicy::new(&[], &["patsy"]).unwrap();
+        // when:
+        let filtered_leaves = leaves(&metadata, false, &policy);
+        let all_leaves = leaves(&metadata, false, &FilterPolicy::all());
+        // then:
+        // `six` is still required by filtered out `patsy`
+        assert_eq!(filtered_leaves, vec!["legacy-pkg"]);
+        assert_eq!(all_leaves, vec!["legacy-pkg", "patsy", "python"])
+    }
+
+    #[test]
+    fn test_dependent_packages_filter_policy() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        let policy = FilterPolicy::new(&[], &["patsy"]).unwrap();
+        // when:
+        let dependents = dependent_packages(&metadata, "six", false, &policy);
+        // then:
+        assert_eq!(dependents, Some(vec![]))
+    }
+
+    #[test]
+    fn test_canonicalize_name() {
+        assert_eq!(canonicalize_name("PyYAML"), "pyyaml");
+        assert_eq!(canonicalize_name("ruamel.yaml"), "ruamel-yaml");
+        assert_eq!(canonicalize_name("ruamel_yaml"), "ruamel-yaml");
+        assert_eq!(canonicalize_name("Foo.-_Bar"), "foo-bar");
+        assert_eq!(canonicalize_name("six"), "six")
+    }
+
+    #[test]
+    fn test_find_metadata() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        // when:
+        let exact = find_metadata(&metadata, "legacy-pkg");
+        let canonical = find_metadata(&metadata, "Legacy_Pkg");
+        let unknown = find_metadata(&metadata, "legacy");
+        // then:
+        assert_eq!(exact.map(|m| m.name.as_str()), Some("legacy-pkg"));
+        assert_eq!(canonical.map(|m| m.name.as_str()), Some("legacy-pkg"));
+        assert_eq!(unknown, None)
+    }
+
+    #[test]
+    fn test_dependent_packages_normalized_name() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mixed-env");
+        // when:
+        let dependents = dependent_packages(&metadata, "ASTOR", false, &FilterPolicy::default());
+        // then:
+        assert_eq!(dependents, Some(vec![String::from("legacy-pkg")]))
+    }
+
+    #[test]
+    fn test_find_metadata_provided_distribution() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let metadata = get_prefix_metadata("./tests/data/mapped-env");
+        // when:
+        let torch = find_metadata(&metadata, "torch");
+        let opencv = find_metadata(&metadata, "opencv-python");
+        // then:
+        assert_eq!(torch.map(|m| m.name.as_str()), Some("pytorch"));
+        assert_eq!(opencv, None)
+    }
+
+    #[test]
+    fn test_read_pypi_mapping() {
+        // given:
+        let path = "./tests/data/mapped-env/pypi-mapping.json";
+        let mut expected_mapping = HashMap::new();
+        expected_mapping.insert(
+            String::from("py-opencv"),
+            vec![String::from("opencv-python")],
+        );
+        // when:
+        let mapping = read_pypi_mapping(path);
+        // then:
+        assert_eq!(mapping.unwrap(), expected_mapping);
+        assert_eq!(
+            read_pypi_mapping("./tests/data/mapped-env/missing.json")
+                .unwrap_err()
+                .to_string(),
+            "Invalid PyPI mapping file './tests/data/mapped-env/missing.json': No such file or directory (os error 2)"
+        );
+        assert!(
+            read_pypi_mapping("./tests/data/mapped-env/conda-meta/pytorch-1.7.0-py3.8_0.json")
+                .is_err()
+        )
+    }
+
+    #[test]
+    fn test_leaves_with_pypi_mapping() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let mut metadata = get_prefix_metadata("./tests/data/mapped-env");
+        let overrides = read_pypi_mapping("./tests/data/mapped-env/pypi-mapping.json").unwrap();
+        // when:
+        let inferred_leaves = leaves(&metadata, false, &FilterPolicy::default());
+        apply_pypi_mapping(&mut metadata, &overrides);
+        let mapped_leaves = leaves(&metadata, false, &FilterPolicy::default());
+        // then:
+        assert_eq!(inferred_leaves, vec!["imgaug", "py-opencv", "torchvision"]);
+        assert_eq!(mapped_leaves, vec!["imgaug", "torchvision"]);
+        assert_eq!(metadata["py-opencv"].pypi_names, vec!["opencv-python"])
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new(
+                "./env/lib/python3.8/site-packages/../../../bin/tool"
+            )),
+            PathBuf::from("env/bin/tool")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../a/./b")),
+            PathBuf::from("../a/b")
+        )
+    }
 }
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::checksum::{sha256_file, Checksum};
+use crate::metadata::Metadata;
+use crate::utils::{find_metadata, get_conda_prefix, relative_path, CONDA_METADATA};
+
+/// Problem found while verifying a file installed by a package.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileProblem {
+    /// File doesn't exist anymore.
+    Missing,
+    /// File has different size than the one recorded during installation.
+    WrongSize { expected: u64, actual: u64 },
+    /// File has the same size, but different checksum than the one recorded during installation.
+    Modified,
+    /// File exists, but it cannot be read.
+    Unreadable(String),
+}
+
+/// File installed by a package, that doesn't match what was recorded during installation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokenFile {
+    /// Path of the file relative to the prefix.
+    pub path: String,
+    pub problem: FileProblem,
+}
+
+/// Package with files that don't match what was recorded during installation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokenPackage {
+    /// Name of the package.
+    pub name: String,
+    /// Broken files of the package, sorted by path.
+    pub files: Vec<BrokenFile>,
+}
+
+impl fmt::Display for FileProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::WrongSize { expected, actual } => {
+                write!(
+                    f,
+                    "wrong size (expected {} bytes, found {} bytes)",
+                    expected, actual
+                )
+            }
+            Self::Modified => write!(f, "modified"),
+            Self::Unreadable(e) => write!(f, "unreadable ({})", e),
+        }
+    }
+}
+
+#[doc(hidden)]
+/// File that is expected to be found in the environment, together with what is known about it.
+#[derive(Clone, Debug, PartialEq)]
+struct ExpectedFile {
+    path: PathBuf,
+    relative: String,
+    size: Option<u64>,
+    checksum: Option<Checksum>,
+}
+
+#[doc(hidden)]
+/// Returns files installed by given package that can be verified.
+///
+/// Checksums and sizes of conda packages are taken from `paths_data` (files with prefix placeholder
+/// are only verified if conda recorded `sha256_in_prefix`, symlinks and directories are only checked for existence).
+/// Checksums and sizes of Python packages installed by pip come from `RECORD` file, compiled files (`.pyc`) are skipped.
+fn get_expected_files<P: AsRef<Path>>(prefix: P, metadata: &Metadata) -> Vec<ExpectedFile> {
+    let prefix = prefix.as_ref();
+    match (&metadata.record, &metadata.location) {
+        (Some(record), _) => match &record.paths_data {
+            Some(paths_data) => paths_data
+                .paths
+                .iter()
+                .map(|p| {
+                    let (size, sha256) = match (&p.path_type[..], &p.prefix_placeholder) {
+                        ("softlink", _) | ("directory", _) => (None, None),
+                        (_, Some(_)) => (None, p.sha256_in_prefix.clone()),
+                        (_, None) => (p.size_in_bytes, p.sha256.clone()),
+                    };
+                    ExpectedFile {
+                        path: prefix.join(&p.path),
+                        relative: p.path.clone(),
+                        size,
+                        checksum: sha256.map(Checksum::Hex),
+                    }
+                })
+                .collect(),
+            None => record
+                .files
+                .iter()
+                .map(|f| ExpectedFile {
+                    path: prefix.join(f),
+                    relative: f.clone(),
+                    size: None,
+                    checksum: None,
+                })
+                .collect(),
+        },
+        (None, Some(location)) => metadata
+            .record_files
+            .iter()
+            .filter(|f| !f.path.ends_with(".pyc"))
+            .map(|f| {
+                let path = location.join(&f.path);
+                ExpectedFile {
+                    relative: relative_path(prefix, &path),
+                    path,
+                    size: f.size,
+                    checksum: f
+                        .hash
+                        .as_ref()
+                        .and_then(|h| h.strip_prefix("sha256="))
+                        .map(|h| Checksum::UrlsafeBase64(h.to_string())),
+                }
+            })
+            .collect(),
+        (None, None) => vec![],
+    }
+}
+
+#[doc(hidden)]
+/// Checks whether given file exists and has expected size and checksum (cheapest checks go first).
+fn verify_file(file: &ExpectedFile) -> Option<FileProblem> {
+    let metadata = match file.path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return Some(FileProblem::Missing),
+    };
+    if let Some(expected) = file.size {
+        if metadata.is_file() && metadata.len() != expected {
+            return Some(FileProblem::WrongSize {
+                expected,
+                actual: metadata.len(),
+            });
+        }
+    }
+    match &file.checksum {
+        Some(checksum) => match sha256_file(&file.path) {
+            Ok(digest) if checksum.matches(&digest) => None,
+            Ok(_) => Some(FileProblem::Modified),
+            Err(e) => Some(FileProblem::Unreadable(e.to_string())),
+        },
+        None => None,
+    }
+}
+
+/// Verifies files installed by given package against checksums and sizes recorded during installation.
+/// Files are hashed in parallel. Returns problems found, sorted by path relative to the prefix.
+pub(crate) fn verify_package<P: AsRef<Path>>(prefix: P, metadata: &Metadata) -> Vec<BrokenFile> {
+    let mut problems: Vec<BrokenFile> = get_expected_files(prefix, metadata)
+        .par_iter()
+        .filter_map(|f| {
+            verify_file(f).map(|problem| BrokenFile {
+                path: f.relative.clone(),
+                problem,
+            })
+        })
+        .collect();
+    problems.sort_by(|a, b| a.path.cmp(&b.path));
+    problems
+}
+
+/// Verifies files of given packages (or all packages of the environment, if none is given).
+/// Returns packages with problems found, sorted by name.
+///
+/// If any of given packages is not installed, then an error is returned.
+pub fn get_verification_problems(names: &[String]) -> Result<Vec<BrokenPackage>, io::Error> {
+    verification_problems(get_conda_prefix(), &CONDA_METADATA, names)
+}
+
+#[doc(hidden)]
+fn verification_problems<P: AsRef<Path> + Sync>(
+    prefix: P,
+    metadata: &HashMap<String, Metadata>,
+    names: &[String],
+) -> Result<Vec<BrokenPackage>, io::Error> {
+    let packages: Vec<&Metadata> = if names.is_empty() {
+        metadata.values().collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                find_metadata(metadata, name).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Package '{}' not found", name),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let mut problems: Vec<BrokenPackage> = packages
+        .par_iter()
+        .map(|m| BrokenPackage {
+            name: m.name.clone(),
+            files: verify_package(&prefix, m),
+        })
+        .filter(|p| !p.files.is_empty())
+        .collect();
+    problems.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_prefix_metadata;
+
+    #[test]
+    fn test_verify_package() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/broken-env";
+        let metadata = get_prefix_metadata(prefix);
+        let expected_problems = vec![
+            BrokenFile {
+                path: String::from("share/pkg-a/missing.txt"),
+                problem: FileProblem::Missing,
+            },
+            BrokenFile {
+                path: String::from("share/pkg-a/modified.txt"),
+                problem: FileProblem::Modified,
+            },
+            BrokenFile {
+                path: String::from("share/pkg-a/truncated.txt"),
+                problem: FileProblem::WrongSize {
+                    expected: 17,
+                    actual: 9,
+                },
+            },
+        ];
+        // when:
+        let problems = verify_package(prefix, metadata.get("pkg-a").unwrap());
+        // then:
+        assert_eq!(problems, expected_problems)
+    }
+
+    #[test]
+    fn test_verify_package_without_paths_data() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/broken-env";
+        let metadata = get_prefix_metadata(prefix);
+        // when:
+        let problems = verify_package(prefix, metadata.get("pkg-b").unwrap());
+        // then:
+        assert_eq!(
+            problems,
+            vec![BrokenFile {
+                path: String::from("share/pkg-b/gone.txt"),
+                problem: FileProblem::Missing
+            }]
+        )
+    }
+
+    #[test]
+    fn test_verify_package_installed_by_pip() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/broken-env";
+        let metadata = get_prefix_metadata(prefix);
+        // when:
+        let problems = verify_package(prefix, metadata.get("edited").unwrap());
+        // then:
+        assert_eq!(
+            problems,
+            vec![BrokenFile {
+                path: String::from("lib/python3.8/site-packages/edited.py"),
+                problem: FileProblem::WrongSize {
+                    expected: 15,
+                    actual: 26
+                }
+            }]
+        )
+    }
+
+    #[test]
+    fn test_verification_problems() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/broken-env";
+        let metadata = get_prefix_metadata(prefix);
+        // when:
+        let all = verification_problems(prefix, &metadata, &[]).unwrap();
+        let selected = verification_problems(prefix, &metadata, &[String::from("pkg-b")]).unwrap();
+        let unknown = verification_problems(prefix, &metadata, &[String::from("numpy")]);
+        // then:
+        let names: Vec<&str> = all.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["edited", "pkg-a", "pkg-b"]);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "pkg-b");
+        assert_eq!(unknown.unwrap_err().kind(), io::ErrorKind::NotFound)
+    }
+
+    #[test]
+    fn test_verification_problems_of_intact_env() {
+        // given:
+        std::env::set_var("CONDA_PREFIX", "./tests/data");
+        let prefix = "./tests/data/mixed-env";
+        let metadata = get_prefix_metadata(prefix);
+        // when:
+        let problems = verification_problems(prefix, &metadata, &[]).unwrap();
+        // then:
+        assert_eq!(problems, vec![])
+    }
+
+    #[test]
+    fn test_file_problem_display() {
+        assert_eq!(FileProblem::Missing.to_string(), "missing");
+        assert_eq!(FileProblem::Modified.to_string(), "modified");
+        assert_eq!(
+            FileProblem::WrongSize {
+                expected: 17,
+                actual: 9
+            }
+            .to_string(),
+            "wrong size (expected 17 bytes, found 9 bytes)"
+        )
+    }
+}
@@ -0,0 +1,650 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref VERSION_CHECK_REGEX: Regex = Regex::new(r"^[*.+!_0-9a-z]+$").unwrap();
+    #[doc(hidden)]
+    static ref VERSION_SPLIT_REGEX: Regex = Regex::new(r"([0-9]+|[*]+|[^0-9*]+)").unwrap();
+}
+
+/// Single part of the version component.
+///
+/// Parts are ordered the same way as in conda: `*` < `dev` < `_` < `a` < number < `post`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Str(String),
+    Int(u64),
+    Inf,
+}
+
+impl Ord for Part {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Part::Str(a), Part::Str(b)) => a.cmp(b),
+            (Part::Str(_), _) => Ordering::Less,
+            (_, Part::Str(_)) => Ordering::Greater,
+            (Part::Int(a), Part::Int(b)) => a.cmp(b),
+            (Part::Int(_), Part::Inf) => Ordering::Less,
+            (Part::Inf, Part::Int(_)) => Ordering::Greater,
+            (Part::Inf, Part::Inf) => Ordering::Equal,
+        }
+    }
+}
+
+impl PartialOrd for Part {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Missing parts and components are filled with `0`, so `1.1` == `1.1.0`.
+const FILL: Part = Part::Int(0);
+
+type Component = Vec<Part>;
+
+/// Conda version, that can be compared with other versions the same way conda does it.
+///
+/// Version string is split into:
+/// - epoch (`1!2.0`), `0` when not given,
+/// - version components, separated by `.`, `_` or `-` (the latter only when there is no `_`),
+/// - local version (`+` suffix), compared only if versions are equal.
+///
+/// Every component is split into runs of numbers and strings,
+/// where `dev` sorts before all other strings and `post` after all numbers.
+#[derive(Clone, Debug)]
+pub struct VersionOrder {
+    norm: String,
+    version: Vec<Component>,
+    local: Vec<Component>,
+}
+
+impl VersionOrder {
+    /// Returns new instance of VersionOrder from given version string.
+    ///
+    /// If an invalid version is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(version: T) -> Result<Self, io::Error> {
+        let original = version.as_ref();
+        let mut norm = original.trim().to_lowercase();
+        if norm.is_empty() {
+            return Err(invalid_version(original, "empty version string"));
+        }
+        if !VERSION_CHECK_REGEX.is_match(&norm) {
+            // dashes are allowed as long as there are no underscores
+            if norm.contains('-') && !norm.contains('_') {
+                norm = norm.replace('-', "_");
+            }
+            if !VERSION_CHECK_REGEX.is_match(&norm) {
+                return Err(invalid_version(original, "invalid character(s)"));
+            }
+        }
+
+        // epoch
+        let (epoch, rest) = match norm.split('!').collect::<Vec<_>>()[..] {
+            [rest] => ("0", rest),
+            [epoch, rest] if !epoch.is_empty() && epoch.chars().all(|c| c.is_ascii_digit()) => {
+                (epoch, rest)
+            }
+            [_, _] => return Err(invalid_version(original, "epoch must be an integer")),
+            _ => return Err(invalid_version(original, "duplicated epoch separator '!'")),
+        };
+
+        // local version
+        let (rest, local) = match rest.split('+').collect::<Vec<_>>()[..] {
+            [rest] => (rest, vec![]),
+            [rest, local] => (
+                rest,
+                local
+                    .replace('_', ".")
+                    .split('.')
+                    .map(String::from)
+                    .collect(),
+            ),
+            _ => {
+                return Err(invalid_version(
+                    original,
+                    "duplicated local version separator '+'",
+                ))
+            }
+        };
+        if rest.is_empty() {
+            return Err(invalid_version(original, "missing version before '+'"));
+        }
+
+        // trailing underscore is kept with the last component (openssl-like versions)
+        let mut split_version: Vec<String> = match rest.strip_suffix('_') {
+            Some(stripped) => {
+                let mut v: Vec<String> = stripped
+                    .replace('_', ".")
+                    .split('.')
+                    .map(String::from)
+                    .collect();
+                if let Some(last) = v.last_mut() {
+                    last.push('_');
+                }
+                v
+            }
+            None => rest
+                .replace('_', ".")
+                .split('.')
+                .map(String::from)
+                .collect(),
+        };
+        split_version.insert(0, epoch.to_string());
+
+        let version = split_version
+            .iter()
+            .map(|c| parse_component(c, original))
+            .collect::<Result<Vec<_>, _>>()?;
+        let local = local
+            .iter()
+            .map(|c| parse_component(c, original))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            norm,
+            version,
+            local,
+        })
+    }
+
+    /// Returns `true` if version starts with given version, component by component,
+    /// e.g. `1.2.3` starts with `1.2`, but `1.20` doesn't.
+    pub fn startswith(&self, other: &VersionOrder) -> bool {
+        let (t1, t2) = if other.local.is_empty() {
+            (&self.version, &other.version)
+        } else {
+            if compare_components(&self.version, &other.version) != Ordering::Equal {
+                return false;
+            }
+            (&self.local, &other.local)
+        };
+        let nt = t2.len() - 1;
+        let head1: Vec<Component> = t1.iter().take(nt).cloned().collect();
+        if compare_components(&head1, &t2[..nt]) != Ordering::Equal {
+            return false;
+        }
+        let empty = vec![];
+        let v1 = t1.get(nt).unwrap_or(&empty);
+        let v2 = &t2[nt];
+        let np = v2.len() - 1;
+        let head1: Component = v1.iter().take(np).cloned().collect();
+        if compare_parts(&head1, &v2[..np]) != Ordering::Equal {
+            return false;
+        }
+        let c1 = v1.get(np).unwrap_or(&FILL);
+        match (c1, &v2[np]) {
+            (Part::Str(s1), Part::Str(s2)) => s1.starts_with(s2.as_str()),
+            (_, Part::Str(_)) => false,
+            (c1, c2) => c1 == c2,
+        }
+    }
+}
+
+impl Ord for VersionOrder {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_components(&self.version, &other.version)
+            .then_with(|| compare_components(&self.local, &other.local))
+    }
+}
+
+impl PartialOrd for VersionOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for VersionOrder {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionOrder {}
+
+impl FromStr for VersionOrder {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VersionOrder::parse(s)
+    }
+}
+
+impl fmt::Display for VersionOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.norm)
+    }
+}
+
+#[doc(hidden)]
+fn invalid_version(version: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid version '{}': {}", version, reason),
+    )
+}
+
+#[doc(hidden)]
+/// Splits version component into runs of numbers and strings.
+/// Component that starts with a letter gets implicit `0` in front of it, so `1.a` < `1.0`.
+fn parse_component(component: &str, original: &str) -> Result<Component, io::Error> {
+    let mut parts = vec![];
+    for m in VERSION_SPLIT_REGEX.find_iter(component) {
+        let part = m.as_str();
+        let part = if part.chars().all(|c| c.is_ascii_digit()) {
+            let number = part
+                .parse::<u64>()
+                .map_err(|_| invalid_version(original, "version component is too large"))?;
+            Part::Int(number)
+        } else if part == "post" {
+            Part::Inf
+        } else if part == "dev" {
+            // upper-cased, so it sorts before all other (lower-cased) strings
+            Part::Str(String::from("DEV"))
+        } else {
+            Part::Str(part.to_string())
+        };
+        parts.push(part);
+    }
+    if parts.is_empty() {
+        return Err(invalid_version(original, "empty version component"));
+    }
+    if component.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        parts.insert(0, Part::Int(0));
+    }
+    Ok(parts)
+}
+
+#[doc(hidden)]
+fn compare_parts(p1: &[Part], p2: &[Part]) -> Ordering {
+    for idx in 0..p1.len().max(p2.len()) {
+        let c1 = p1.get(idx).unwrap_or(&FILL);
+        let c2 = p2.get(idx).unwrap_or(&FILL);
+        match c1.cmp(c2) {
+            Ordering::Equal => continue,
+            ordering => return ordering,
+        }
+    }
+    Ordering::Equal
+}
+
+#[doc(hidden)]
+fn compare_components(t1: &[Component], t2: &[Component]) -> Ordering {
+    let empty = vec![];
+    for idx in 0..t1.len().max(t2.len()) {
+        let v1 = t1.get(idx).unwrap_or(&empty);
+        let v2 = t2.get(idx).unwrap_or(&empty);
+        match compare_parts(v1, v2) {
+            Ordering::Equal => continue,
+            ordering => return ordering,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Version relation operators supported by conda.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    StartsWith,
+    NotStartsWith,
+    Compatible,
+}
+
+/// Conda version constraint, e.g. `>=1.19,<2.0a0`, `1.2.*` or `>=1,<2|3.*`.
+///
+/// `,` means AND, `|` means OR (with lower precedence), parentheses can be used for grouping.
+#[derive(Clone, Debug)]
+pub enum VersionSpec {
+    Any,
+    Relation(Operator, VersionOrder),
+    Regex(Regex),
+    And(Vec<VersionSpec>),
+    Or(Vec<VersionSpec>),
+}
+
+impl VersionSpec {
+    /// Returns new instance of VersionSpec from given version constraint.
+    ///
+    /// If an invalid constraint is given, then an error is returned.
+    pub fn parse<T: AsRef<str>>(spec: T) -> Result<Self, io::Error> {
+        let original = spec.as_ref();
+        let spec: String = original.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut parser = SpecParser {
+            original,
+            spec: &spec,
+            pos: 0,
+        };
+        let version_spec = parser.parse_or()?;
+        if parser.pos != spec.len() {
+            return Err(invalid_version(original, "unexpected characters"));
+        }
+        Ok(version_spec)
+    }
+
+    /// Returns `true` if given version satisfies the constraint.
+    pub fn matches(&self, version: &VersionOrder) -> bool {
+        match self {
+            VersionSpec::Any => true,
+            VersionSpec::Relation(operator, other) => match operator {
+                Operator::Eq => version == other,
+                Operator::Ne => version != other,
+                Operator::Lt => version < other,
+                Operator::Le => version <= other,
+                Operator::Gt => version > other,
+                Operator::Ge => version >= other,
+                Operator::StartsWith => version.startswith(other),
+                Operator::NotStartsWith => !version.startswith(other),
+                Operator::Compatible => {
+                    let prefix = match other.norm.rsplit_once('.') {
+                        Some((prefix, _)) => prefix,
+                        None => other.norm.as_str(),
+                    };
+                    version >= other
+                        && VersionOrder::parse(prefix)
+                            .map(|p| version.startswith(&p))
+                            .unwrap_or(false)
+                }
+            },
+            VersionSpec::Regex(regex) => regex.is_match(&version.norm),
+            VersionSpec::And(specs) => specs.iter().all(|s| s.matches(version)),
+            VersionSpec::Or(specs) => specs.iter().any(|s| s.matches(version)),
+        }
+    }
+}
+
+impl FromStr for VersionSpec {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VersionSpec::parse(s)
+    }
+}
+
+#[doc(hidden)]
+/// Simple recursive descent parser of the version constraints.
+struct SpecParser<'a> {
+    original: &'a str,
+    spec: &'a str,
+    pos: usize,
+}
+
+impl<'a> SpecParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.spec[self.pos..].chars().next()
+    }
+
+    fn parse_or(&mut self) -> Result<VersionSpec, io::Error> {
+        let mut specs = vec![self.parse_and()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            specs.push(self.parse_and()?);
+        }
+        Ok(if specs.len() == 1 {
+            specs.remove(0)
+        } else {
+            VersionSpec::Or(specs)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<VersionSpec, io::Error> {
+        let mut specs = vec![self.parse_atom()?];
+        while self.peek() == Some(',') {
+            self.pos += 1;
+            specs.push(self.parse_atom()?);
+        }
+        Ok(if specs.len() == 1 {
+            specs.remove(0)
+        } else {
+            VersionSpec::And(specs)
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<VersionSpec, io::Error> {
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            let spec = self.parse_or()?;
+            if self.peek() != Some(')') {
+                return Err(invalid_version(self.original, "unbalanced parentheses"));
+            }
+            self.pos += 1;
+            return Ok(spec);
+        }
+        let end = self.spec[self.pos..]
+            .find(|c: char| ",|()".contains(c))
+            .map(|idx| self.pos + idx)
+            .unwrap_or(self.spec.len());
+        let atom = &self.spec[self.pos..end];
+        self.pos = end;
+        parse_relation(atom, self.original)
+    }
+}
+
+#[doc(hidden)]
+/// Parses single version relation, e.g. `>=1.19`, `1.2.*` or `1.2.3`.
+fn parse_relation(atom: &str, original: &str) -> Result<VersionSpec, io::Error> {
+    if atom.is_empty() {
+        return Err(invalid_version(original, "empty version constraint"));
+    }
+    if atom.trim_end_matches('*').is_empty() {
+        return Ok(VersionSpec::Any);
+    }
+    let operators = [
+        ("==", Operator::Eq),
+        ("!=", Operator::Ne),
+        ("<=", Operator::Le),
+        (">=", Operator::Ge),
+        ("~=", Operator::Compatible),
+        ("<", Operator::Lt),
+        (">", Operator::Gt),
+        ("=", Operator::StartsWith),
+    ];
+    for (op_str, operator) in operators.iter() {
+        if let Some(version) = atom.strip_prefix(op_str) {
+            if version.is_empty() || version.starts_with(|c: char| "=<>!~".contains(c)) {
+                return Err(invalid_version(original, "invalid operator"));
+            }
+            let mut operator = operator.clone();
+            let version = match version
+                .strip_suffix(".*")
+                .or_else(|| version.strip_suffix('*'))
+            {
+                Some(stripped) => {
+                    match operator {
+                        Operator::Ne => operator = Operator::NotStartsWith,
+                        Operator::Eq => operator = Operator::StartsWith,
+                        Operator::Compatible => {
+                            return Err(invalid_version(original, "invalid operator with '.*'"))
+                        }
+                        // `.*` is superfluous with other operators
+                        _ => (),
+                    }
+                    stripped
+                }
+                None => version,
+            };
+            return Ok(VersionSpec::Relation(
+                operator,
+                VersionOrder::parse(version)?,
+            ));
+        }
+    }
+    if atom.trim_end_matches('*').contains('*') {
+        // glob in the middle of the version, e.g. `1.*.3`
+        let regex = format!("^(?:{})$", regex::escape(atom).replace(r"\*", ".*"));
+        let regex = Regex::new(&regex).map_err(|e| invalid_version(original, &e.to_string()))?;
+        return Ok(VersionSpec::Regex(regex));
+    }
+    if let Some(prefix) = atom.strip_suffix('*') {
+        let prefix = prefix.trim_end_matches('.');
+        return Ok(VersionSpec::Relation(
+            Operator::StartsWith,
+            VersionOrder::parse(prefix)?,
+        ));
+    }
+    Ok(VersionSpec::Relation(
+        Operator::Eq,
+        VersionOrder::parse(atom)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> VersionOrder {
+        VersionOrder::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_version_order() {
+        // ordering taken from conda's VersionOrder documentation
+        let versions = vec![
+            "0.4",
+            "0.4.0",
+            "0.4.1a.vc11",
+            "0.4.1.rc",
+            "0.4.1.RC",
+            "0.4.1",
+            "0.5a1",
+            "0.5b3",
+            "0.5C1",
+            "0.5z",
+            "0.5",
+            "0.9.6",
+            "0.960923",
+            "1.0",
+            "1.1dev1",
+            "1.1_",
+            "1.1a1",
+            "1.1.0dev1",
+            "1.1.a1",
+            "1.1.0rc1",
+            "1.1.0",
+            "1.1.0post1",
+            "1.1post1",
+            "1996.07.12",
+            "1!0.4.1",
+            "1!3.1.1.6",
+            "2!0.4.1",
+        ];
+        for pair in versions.windows(2) {
+            assert!(v(pair[0]) <= v(pair[1]), "{} <= {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_version_order_equal() {
+        assert_eq!(v("1.1"), v("1.1.0"));
+        assert_eq!(v("0.4"), v("0.4.0"));
+        assert_eq!(v("0.4.1.rc"), v("0.4.1.RC"));
+        assert_eq!(v("1.0-1"), v("1.0_1"));
+        assert_ne!(v("1.0"), v("1.0+1"));
+    }
+
+    #[test]
+    fn test_version_order_local() {
+        assert!(v("1.0+1") < v("1.0+2"));
+        assert!(v("1.0") < v("1.0+1"));
+        assert!(v("1.0+abc") < v("1.1"));
+    }
+
+    #[test]
+    fn test_version_order_sort() {
+        // given:
+        let mut versions = [v("1.10"), v("1.9"), v("1.9.post1"), v("1.9rc1"), v("1!0.1")];
+        // when:
+        versions.sort();
+        // then:
+        let sorted: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+        assert_eq!(sorted, vec!["1.9rc1", "1.9", "1.9.post1", "1.10", "1!0.1"])
+    }
+
+    #[test]
+    fn test_version_order_invalid() {
+        let invalid_versions = vec![
+            "", "1.0$", "1!2!3", "a!1.0", "1+2+3", "+1", "1..0", "1.0-1_1",
+        ];
+        for version in invalid_versions {
+            assert!(
+                VersionOrder::parse(version).is_err(),
+                "'{}' should be invalid",
+                version
+            )
+        }
+    }
+
+    #[test]
+    fn test_startswith() {
+        assert!(v("1.2.3").startswith(&v("1.2")));
+        assert!(v("1.2").startswith(&v("1.2")));
+        assert!(v("1.2a").startswith(&v("1.2a")));
+        assert!(!v("1.20").startswith(&v("1.2")));
+        assert!(!v("1.3").startswith(&v("1.2")));
+        assert!(v("1.2+abc.1").startswith(&v("1.2+abc")));
+    }
+
+    #[test]
+    fn test_version_spec_matches() {
+        let cases = vec![
+            ("*", "1.0", true),
+            ("1.0", "1.0.0", true),
+            ("1.0", "1.0.1", false),
+            ("==1.0", "1.0", true),
+            ("!=1.0", "1.0", false),
+            (">=1.19,<2.0a0", "1.19.1", true),
+            (">=1.19,<2.0a0", "2.0", false),
+            (">=1.19,<2.0a0", "2.0a0", false),
+            (">=1.19,<2.0a0", "1.18", false),
+            (">=1,<2|3.*", "1.5", true),
+            (">=1,<2|3.*", "2.5", false),
+            (">=1,<2|3.*", "3.1", true),
+            ("1.2.*", "1.2.3", true),
+            ("1.2.*", "1.20", false),
+            ("1.2*", "1.2.3", true),
+            ("=1.2", "1.2.3", true),
+            ("!=1.2.*", "1.2.3", false),
+            ("!=1.2.*", "1.3", true),
+            ("~=1.2.3", "1.2.5", true),
+            ("~=1.2.3", "1.3", false),
+            ("~=1.2.3", "1.2.1", false),
+            ("1.*.3", "1.2.3", true),
+            ("1.*.3", "1.2.4", false),
+            ("(>=1,<2)|(>3,<4)", "3.5", true),
+            ("(>=1,<2)|(>3,<4)", "2.5", false),
+            (">= 1.0 , < 2.0", "1.5", true),
+        ];
+        for (spec, version, expected) in cases {
+            let version_spec = VersionSpec::parse(spec).unwrap();
+            assert_eq!(
+                version_spec.matches(&v(version)),
+                expected,
+                "'{}' matches '{}'",
+                spec,
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_spec_invalid() {
+        let invalid_specs = vec!["", ">=", "1.0,", "(>=1.0", ">=1.0)", "~=1.2.*", "=>1.0"];
+        for spec in invalid_specs {
+            assert!(
+                VersionSpec::parse(spec).is_err(),
+                "'{}' should be invalid",
+                spec
+            )
+        }
+    }
+}
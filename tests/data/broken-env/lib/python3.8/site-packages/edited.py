@@ -0,0 +1 @@
+print('patched in place')
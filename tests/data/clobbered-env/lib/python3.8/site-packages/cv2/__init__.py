@@ -0,0 +1 @@
+# opencv-python
@@ -0,0 +1 @@
+def get(url): pass
@@ -0,0 +1 @@
+"""OS routines for NT or Posix."""
@@ -0,0 +1,2 @@
+"""Utilities for writing code that runs on Python 2 and 3"""
+__version__ = "1.15.0"
@@ -0,0 +1 @@
+__version__ = "5.4.1"